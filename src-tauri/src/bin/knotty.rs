@@ -1,8 +1,13 @@
 use clap::{Parser, Subcommand};
-use knotwork_lib::workflow::{Flow, WorkflowEngine};
+use knotwork_lib::checkpoint::Checkpoint;
+use knotwork_lib::migration;
+use knotwork_lib::validation::Diagnostic;
+use knotwork_lib::workflow::{ExecutionEvent, ExecutionStatus, Flow, FlowRun, WorkflowEngine};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 use axum::{
     extract::{State, Query},
     http::{StatusCode, HeaderMap},
@@ -26,12 +31,48 @@ enum Commands {
         /// Path to the flow JSON file
         #[arg(short, long)]
         file: Option<PathBuf>,
+        /// Maximum number of independent nodes executed at the same time
+        #[arg(long, default_value_t = knotwork_lib::workflow::DEFAULT_MAX_CONCURRENCY)]
+        max_concurrency: usize,
+        /// Abort the run after this many seconds (overrides the flow's own timeout)
+        #[arg(long, value_name = "SECS")]
+        timeout: Option<u64>,
+        /// Save a checkpoint to this file after every node so the run can be resumed
+        #[arg(long, value_name = "FILE")]
+        checkpoint: Option<PathBuf>,
+        /// Continue the run saved in this checkpoint file (and keep checkpointing to it); -p values replace saved ones
+        #[arg(long, value_name = "FILE")]
+        resume: Option<PathBuf>,
+        /// Value for a flow parameter; missing required ones are prompted for
+        #[arg(short = 'p', long = "param", value_name = "NAME=VALUE")]
+        params: Vec<String>,
+        /// Seed for random template values ($uuid, $randomInt, ...) to make the run reproducible
+        #[arg(long)]
+        seed: Option<u64>,
     },
     /// Start a mock server from a flow
     Serve {
         /// Path to the flow JSON file
         #[arg(short, long)]
         file: PathBuf,
+    },
+    /// Check a flow for problems without running it
+    Validate {
+        /// Path to the flow JSON file
+        #[arg(short, long)]
+        file: PathBuf,
+        /// Also check the requirements of `knotty serve`
+        #[arg(long)]
+        serve: bool,
+    },
+    /// Upgrade flow files to the current format, keeping a .bak copy of each
+    Migrate {
+        /// Flow JSON files to upgrade in place
+        #[arg(short, long, required = true, num_args = 1..)]
+        file: Vec<PathBuf>,
+        /// Only report what would change
+        #[arg(long)]
+        dry_run: bool,
     }
 }
 
@@ -53,18 +94,24 @@ async fn main() -> anyhow::Result<()> {
         Some(Commands::Serve { file }) => {
             handle_serve(file).await?;
         },
-        Some(Commands::Run { file }) => {
-            handle_run(file).await?;
+        Some(Commands::Validate { file, serve }) => {
+            handle_validate(file, serve)?;
+        },
+        Some(Commands::Migrate { file, dry_run }) => {
+            handle_migrate(file, dry_run)?;
+        },
+        Some(Commands::Run { file, max_concurrency, timeout, checkpoint, resume, params, seed }) => {
+            handle_run(file, max_concurrency, timeout, checkpoint, resume, params, seed).await?;
         },
         None => {
-            handle_run(None).await?;
+            handle_run(None, knotwork_lib::workflow::DEFAULT_MAX_CONCURRENCY, None, None, None, Vec::new(), None).await?;
         }
     }
 
     Ok(())
 }
 
-async fn handle_run(file: Option<PathBuf>) -> anyhow::Result<()> {
+async fn handle_run(file: Option<PathBuf>, max_concurrency: usize, timeout: Option<u64>, checkpoint: Option<PathBuf>, resume: Option<PathBuf>, params: Vec<String>, seed: Option<u64>) -> anyhow::Result<()> {
     let flow_path = if let Some(path) = file {
         path
     } else {
@@ -101,22 +148,89 @@ async fn handle_run(file: Option<PathBuf>) -> anyhow::Result<()> {
 
     println!("\x1b[1;34m[INFO]\x1b[0m Loading flow from: {:?}", flow_path);
 
-    let content = fs::read_to_string(&flow_path)?;
-    let flow: Flow = serde_json::from_str(&content)?;
+    let flow = Flow::load(&flow_path)?;
+
+    if !report_diagnostics(&flow.validate(), Diagnostic::blocks_run) {
+        anyhow::bail!("Flow has validation errors; fix them before running.");
+    }
+
+    let mut variables = HashMap::new();
+    for param in params {
+        let (name, value) = param.split_once('=').ok_or_else(|| anyhow::anyhow!("Expected NAME=VALUE, got '{}'", param))?;
+        variables.insert(name.to_string(), serde_json::Value::String(value.to_string()));
+    }
+    // A resumed run already has its variables, except the secret ones; values
+    // given with -p replace the saved ones
+    let saved = match &resume {
+        Some(path) => {
+            let mut saved = Checkpoint::load(path)?;
+            saved.variables.extend(variables.drain());
+            prompt_parameters(&flow, &mut saved.variables)?;
+            flow.bind_secret_parameters(&mut saved.variables)?;
+            Some(saved)
+        }
+        None => {
+            prompt_parameters(&flow, &mut variables)?;
+            None
+        }
+    };
 
     println!("\x1b[1;34m[INFO]\x1b[0m Executing flow with {} nodes...", flow.nodes.len());
 
-    let engine = WorkflowEngine::new();
-    let (results, _variables) = engine.execute(&flow, std::collections::HashMap::new()).await?;
+    let mut engine = WorkflowEngine::new().with_max_concurrency(max_concurrency).with_flow_file(&flow_path);
+    if let Some(secs) = timeout {
+        engine = engine.with_flow_timeout(std::time::Duration::from_secs(secs));
+    }
+    if let Some(seed) = seed {
+        engine = engine.with_seed(seed);
+    }
+    if let Some(path) = checkpoint.or_else(|| resume.clone()) {
+        engine = engine.with_checkpoints(path);
+    }
+    let engine = engine
+        .with_observer(|event: &ExecutionEvent| match event {
+            ExecutionEvent::NodeStarted { node_id, node_type } => {
+                println!("  \x1b[2m-> {} ({})\x1b[0m", node_id, node_type);
+            },
+            ExecutionEvent::NodeFinished { result } if result.is_error() => {
+                println!("  \x1b[31mx {}\x1b[0m {}", result.node_id, result.error.as_deref().unwrap_or(""));
+            },
+            _ => {}
+        });
+    // Ctrl-C stops in-flight requests and prints whatever finished
+    let cancel = CancellationToken::new();
+    let on_interrupt = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            println!("\n\x1b[1;33m[WARN]\x1b[0m Interrupted, cancelling flow...");
+            on_interrupt.cancel();
+        }
+    });
 
-    println!("\x1b[1;32m[SUCCESS]\x1b[0m Execution Complete!");
+    let FlowRun { results, outputs, .. } = match (saved, &resume) {
+        (Some(saved), Some(path)) => {
+            println!("\x1b[1;34m[INFO]\x1b[0m Resuming from {:?} ({} nodes already ran)", path, saved.results.len());
+            engine.resume(&flow, saved, &cancel).await?
+        }
+        _ => engine.execute(&flow, variables, &cancel).await?,
+    };
+
+    if cancel.is_cancelled() {
+        println!("\x1b[1;33m[CANCELLED]\x1b[0m Execution stopped, partial results below.");
+    } else {
+        println!("\x1b[1;32m[SUCCESS]\x1b[0m Execution Complete!");
+    }
     println!("\n--- Results ---");
     
     let mut has_error = false;
     for (node_id, result) in results {
-        let status_color = if result.status == "success" { "\x1b[32m" } else { "\x1b[31m" };
+        let status_color = match result.status {
+            ExecutionStatus::Success => "\x1b[32m",
+            ExecutionStatus::Cancelled | ExecutionStatus::Skipped => "\x1b[33m",
+            _ => "\x1b[31m",
+        };
         println!("\x1b[1mNode {}\x1b[0m: Status: {}{}\x1b[0m", node_id, status_color, result.status);
-        if result.status == "error" {
+        if result.is_error() {
             has_error = true;
             println!("  \x1b[31mError:\x1b[0m {:?}", result.error);
         } else {
@@ -130,17 +244,112 @@ async fn handle_run(file: Option<PathBuf>) -> anyhow::Result<()> {
     }
     println!("---------------\n");
 
+    if !flow.outputs.is_empty() {
+        println!("--- Outputs ---");
+        for output in &flow.outputs {
+            match outputs.get(&output.name) {
+                Some(value) => println!("\x1b[1m{}\x1b[0m: {}", output.name, value),
+                None => println!("\x1b[1;33m[WARN]\x1b[0m Output '{}' was not set", output.name),
+            }
+        }
+    }
     println!("---------------\n");
+    if cancel.is_cancelled() {
+        std::process::exit(130);
+    }
     if has_error {
         std::process::exit(1);
     }
     Ok(())
 }
 
+/// Asks for every required parameter that has neither a value nor a default.
+fn prompt_parameters(flow: &Flow, variables: &mut HashMap<String, serde_json::Value>) -> anyhow::Result<()> {
+    use dialoguer::{theme::ColorfulTheme, Input, Password};
+    use std::io::IsTerminal;
+
+    // Without a terminal (CI) the run fails on the missing parameters instead
+    if !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let theme = ColorfulTheme::default();
+    for param in flow.missing_parameters(variables) {
+        let prompt = match &param.description {
+            Some(description) => format!("{} ({})", param.name, description),
+            None => param.name.clone(),
+        };
+        let value = if param.secret {
+            Password::with_theme(&theme).with_prompt(prompt).interact()?
+        } else {
+            Input::<String>::with_theme(&theme).with_prompt(prompt).interact_text()?
+        };
+        variables.insert(param.name.clone(), serde_json::Value::String(value));
+    }
+    Ok(())
+}
+
+fn handle_migrate(files: Vec<PathBuf>, dry_run: bool) -> anyhow::Result<()> {
+    for file in files {
+        let content = fs::read_to_string(&file)?;
+        let migrated = migration::migrate(serde_json::from_str(&content)?)?;
+        if migrated.applied.is_empty() {
+            println!("\x1b[1;34m[INFO]\x1b[0m {:?} is already at version {}", file, migration::CURRENT_VERSION);
+            continue;
+        }
+
+        println!("\x1b[1;34m[INFO]\x1b[0m {:?}: version {} -> {}", file, migrated.from_version, migration::CURRENT_VERSION);
+        for step in &migrated.applied {
+            println!("  - {}", step);
+        }
+        // Make sure the result is a flow this build can run before touching the file
+        serde_json::from_value::<Flow>(migrated.document.clone())?;
+        if dry_run {
+            continue;
+        }
+
+        let backup = PathBuf::from(format!("{}.bak", file.display()));
+        fs::copy(&file, &backup)?;
+        fs::write(&file, serde_json::to_string_pretty(&migrated.document)?)?;
+        println!("\x1b[1;32m[MIGRATED]\x1b[0m Backup saved to {:?}", backup);
+    }
+    Ok(())
+}
+
+fn handle_validate(file: PathBuf, serve: bool) -> anyhow::Result<()> {
+    println!("\x1b[1;34m[INFO]\x1b[0m Validating flow: {:?}", file);
+    let flow = Flow::load(&file)?;
+
+    let diagnostics = if serve { flow.validate_for_serve() } else { flow.validate() };
+    if diagnostics.is_empty() {
+        println!("\x1b[1;32m[OK]\x1b[0m No problems found.");
+        return Ok(());
+    }
+    if !report_diagnostics(&diagnostics, Diagnostic::is_error) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Prints diagnostics, the `fatal` ones as errors and the rest as warnings,
+/// and returns false if any of them is fatal.
+fn report_diagnostics(diagnostics: &[Diagnostic], fatal: fn(&Diagnostic) -> bool) -> bool {
+    for diagnostic in diagnostics {
+        if fatal(diagnostic) {
+            println!("\x1b[1;31m[ERROR]\x1b[0m {}", diagnostic);
+        } else {
+            println!("\x1b[1;33m[WARN]\x1b[0m {}", diagnostic);
+        }
+    }
+    !diagnostics.iter().any(fatal)
+}
+
 async fn handle_serve(file: PathBuf) -> anyhow::Result<()> {
     println!("\x1b[1;34m[INFO]\x1b[0m Loading flow for server mode from: {:?}", file);
-    let content = fs::read_to_string(&file)?;
-    let flow: Flow = serde_json::from_str(&content)?;
+    let flow = Flow::load(&file)?;
+
+    if !report_diagnostics(&flow.validate_for_serve(), Diagnostic::blocks_run) {
+        anyhow::bail!("Flow has validation errors; cannot start server.");
+    }
 
     // Find ServerTrigger node to get config
     let trigger_node = flow.nodes.iter().find(|n| n.node_type == "serverTrigger");
@@ -157,7 +366,7 @@ async fn handle_serve(file: PathBuf) -> anyhow::Result<()> {
     println!("\x1b[1;32m[START]\x1b[0m Starting Mock Server on path {} with method {} on port {}", path, method, port);
 
     // Create shared state
-    let state = Arc::new(flow);
+    let state = Arc::new(ServedFlow { flow, file });
 
     let app = Router::new()
         .route(&path, any(handle_request))
@@ -169,9 +378,15 @@ async fn handle_serve(file: PathBuf) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// The flow answering requests in serve mode, with the file it was loaded from.
+struct ServedFlow {
+    flow: Flow,
+    file: PathBuf,
+}
+
 // Handler for the server
 async fn handle_request(
-    State(flow): State<Arc<Flow>>,
+    State(served): State<Arc<ServedFlow>>,
     method: axum::http::Method,
     Query(params): Query<std::collections::HashMap<String, String>>,
     // Headers? Body? For now keeping it simple.
@@ -179,6 +394,7 @@ async fn handle_request(
     body: axum::body::Bytes,
 ) -> impl IntoResponse {
     let body_str = String::from_utf8_lossy(&body).to_string();
+    let flow = &served.flow;
     
     // Inject request data into the flow execution context
     // We need to pre-populate map with the 'serverTrigger' output so subsequent nodes can use it.
@@ -188,22 +404,17 @@ async fn handle_request(
     
     use knotwork_lib::workflow::ExecutionResult;
     
-    initial_results.insert(trigger_node.id.clone(), ExecutionResult {
-        node_id: trigger_node.id.clone(),
-        status: "success".to_string(),
-        output: serde_json::json!({
-            "method": method.to_string(),
-            "query": params,
-            "body": body_str,
-            // "headers": ...
-        }),
-        error: None,
-        active_handle: None,
-    });
+    initial_results.insert(trigger_node.id.clone(), ExecutionResult::success(trigger_node.id.clone(), serde_json::json!({
+        "method": method.to_string(),
+        "query": params,
+        "body": body_str,
+        // "headers": ...
+    })));
 
     println!("-> request received: {} {}", method, flow.nodes.len());
 
-    let engine = WorkflowEngine::new();
+    // Subflow paths are relative to the served flow's file, as in `knotty run`
+    let engine = WorkflowEngine::new().with_flow_file(&served.file);
     // We need a way to pass initial_results to execute. 
     // Currently execute() creates its own results map. 
     // Wait, check workflow.rs signature. 
@@ -234,8 +445,8 @@ async fn handle_request(
     // Or just modify `serverTrigger` execution in `workflow.rs` to pull from variables!
     // Yes.
     
-    match engine.execute(&flow, variables).await {
-        Ok((results, _)) => {
+    match engine.execute(flow, variables, &CancellationToken::new()).await {
+        Ok(FlowRun { results, .. }) => {
             // Find serverResponse node result
             if let Some((_, res)) = results.iter().find(|(id, _)| {
                if let Some(n) = flow.nodes.iter().find(|n| &n.id == *id) {
@@ -0,0 +1,176 @@
+//! Saved run state that lets a failed or interrupted run pick up where it stopped.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::workflow::ExecutionResult;
+
+/// A node waiting to run, with the ids of the edges that dispatched it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedNode {
+    pub node_id: String,
+    pub arrived: Vec<String>,
+}
+
+/// Everything the engine needs to continue a run: what already ran, the
+/// variables, and the scheduling queue. Loop nodes work out their next index
+/// from their previous result, so restoring `results` restores loop positions.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub results: HashMap<String, ExecutionResult>,
+    /// The run's variables, without the values of `secret` parameters; those
+    /// have to be given again to resume.
+    pub variables: HashMap<String, serde_json::Value>,
+    /// Saved to its own file next to the checkpoint (see [`CheckpointWriter`]).
+    #[serde(default, skip_serializing)]
+    pub trace: Vec<ExecutionResult>,
+    /// How many entries of the trace file belong to this checkpoint.
+    #[serde(default)]
+    pub(crate) trace_len: usize,
+    /// Nodes whose last run failed; they run first when the checkpoint is resumed.
+    pub failed: Vec<QueuedNode>,
+    /// The unstarted rest of the wave in progress.
+    pub current: Vec<QueuedNode>,
+    /// Loop nodes to run again once the current wave is done.
+    pub requeued: Vec<QueuedNode>,
+    /// Dispatches of the current wave that still have to pass their join.
+    pub arrivals: Vec<QueuedNode>,
+    /// Waves still to run, the last one first.
+    pub waves: Vec<Vec<QueuedNode>>,
+    /// Edges that have reached each `all`/`first` join so far.
+    pub pending_joins: HashMap<String, Vec<String>>,
+    /// How many times each node has run.
+    pub visits: HashMap<String, usize>,
+}
+
+impl Checkpoint {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read_to_string(path).with_context(|| format!("Failed to read checkpoint {:?}", path))?;
+        let mut checkpoint: Checkpoint = serde_json::from_str(&data).with_context(|| format!("Failed to parse checkpoint {:?}", path))?;
+
+        // Entries past `trace_len` were appended by a save that didn't finish.
+        if checkpoint.trace_len > 0 {
+            let trace_path = trace_path(path);
+            let data = fs::read_to_string(&trace_path).with_context(|| format!("Failed to read checkpoint trace {:?}", trace_path))?;
+            checkpoint.trace = data.lines()
+                .take(checkpoint.trace_len)
+                .map(serde_json::from_str)
+                .collect::<Result<_, _>>()
+                .with_context(|| format!("Failed to parse checkpoint trace {:?}", trace_path))?;
+            if checkpoint.trace.len() < checkpoint.trace_len {
+                bail!("Checkpoint trace {:?} is missing entries", trace_path);
+            }
+        }
+        Ok(checkpoint)
+    }
+
+    /// Deletes the checkpoint at `path` and its trace, if they exist.
+    pub fn remove(path: impl AsRef<Path>) {
+        let path = path.as_ref();
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(trace_path(path));
+    }
+}
+
+/// Saves the checkpoints of one run to the same file. The trace only grows, so
+/// it goes to a JSON Lines file next to the checkpoint and each save appends
+/// just the entries added since the one before.
+pub struct CheckpointWriter {
+    path: PathBuf,
+    /// Trace entries already in the trace file; `None` until the first save rewrites it.
+    traced: Option<usize>,
+}
+
+impl CheckpointWriter {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), traced: None }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saves `checkpoint` with `trace` as its trace. The checkpoint itself is
+    /// written next to its path first and renamed into place, so a crash
+    /// mid-write never leaves a truncated checkpoint behind.
+    pub fn save(&mut self, mut checkpoint: Checkpoint, trace: &[ExecutionResult]) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+
+        let trace_path = trace_path(&self.path);
+        let traced = self.traced.take();
+        let mut lines = String::new();
+        for result in &trace[traced.unwrap_or(0).min(trace.len())..] {
+            lines.push_str(&serde_json::to_string(result)?);
+            lines.push('\n');
+        }
+        let mut file = OpenOptions::new().create(true).write(true).append(traced.is_some()).truncate(traced.is_none()).open(&trace_path)
+            .with_context(|| format!("Failed to open checkpoint trace {:?}", trace_path))?;
+        file.write_all(lines.as_bytes()).with_context(|| format!("Failed to write checkpoint trace {:?}", trace_path))?;
+        self.traced = Some(trace.len());
+
+        checkpoint.trace_len = trace.len();
+        let tmp = self.path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string(&checkpoint)?).with_context(|| format!("Failed to write checkpoint {:?}", tmp))?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// The trace file kept next to the checkpoint at `path`.
+fn trace_path(path: &Path) -> PathBuf {
+    path.with_extension("trace.jsonl")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("knotwork-checkpoint-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("run.json")
+    }
+
+    fn result(id: &str) -> ExecutionResult {
+        ExecutionResult::success(id, serde_json::json!({ "id": id }))
+    }
+
+    #[test]
+    fn saves_append_to_the_trace() {
+        let path = temp_path("append");
+        let mut writer = CheckpointWriter::new(&path);
+        let trace = [result("a"), result("b"), result("c")];
+
+        writer.save(Checkpoint::default(), &trace[..1]).unwrap();
+        writer.save(Checkpoint::default(), &trace[..3]).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        assert_eq!(loaded.trace.iter().map(|r| r.node_id.as_str()).collect::<Vec<_>>(), ["a", "b", "c"]);
+        assert_eq!(fs::read_to_string(trace_path(&path)).unwrap().lines().count(), 3);
+
+        // A new writer starts the trace over.
+        CheckpointWriter::new(&path).save(Checkpoint::default(), &trace[..2]).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap().trace.len(), 2);
+
+        Checkpoint::remove(&path);
+        assert!(!path.exists() && !trace_path(&path).exists());
+    }
+
+    #[test]
+    fn load_ignores_entries_of_an_unfinished_save() {
+        let path = temp_path("unfinished");
+        let mut writer = CheckpointWriter::new(&path);
+        writer.save(Checkpoint::default(), &[result("a")]).unwrap();
+        let mut trace = OpenOptions::new().append(true).open(trace_path(&path)).unwrap();
+        writeln!(trace, "{{\"truncated").unwrap();
+
+        assert_eq!(Checkpoint::load(&path).unwrap().trace.len(), 1);
+        Checkpoint::remove(&path);
+    }
+}
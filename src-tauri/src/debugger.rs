@@ -0,0 +1,123 @@
+//! Step-through debugging for [`WorkflowEngine`](crate::workflow::WorkflowEngine).
+//!
+//! Attach a [`Debugger`] with `WorkflowEngine::with_debugger` and the engine
+//! pauses before every node whose `data.breakpoint` is true, and before every
+//! node after a [`Debugger::step`]. While paused, the same handle (it is cheap
+//! to clone) inspects the run, edits variables and resumes it.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::workflow::{ExecutionResult, Node};
+
+/// What the engine exposes while it waits in front of a node.
+#[derive(Debug, Clone, Serialize)]
+pub struct PausedState {
+    /// The node that runs next.
+    pub node_id: String,
+    pub variables: HashMap<String, serde_json::Value>,
+    pub results: HashMap<String, ExecutionResult>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Resume {
+    Step,
+    Continue,
+}
+
+#[derive(Default)]
+struct State {
+    paused: Option<PausedState>,
+    resume: Option<Resume>,
+    stepping: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct Debugger {
+    state: Arc<Mutex<State>>,
+    notify: Arc<Notify>,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Snapshot of the paused run, or `None` while it is running.
+    pub fn paused(&self) -> Option<PausedState> {
+        self.state.lock().unwrap().paused.clone()
+    }
+
+    /// Changes a variable of the paused run before the next node sees it.
+    pub fn set_variable(&self, name: String, value: serde_json::Value) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        let paused = state.paused.as_mut().ok_or("The flow is not paused")?;
+        paused.variables.insert(name, value);
+        Ok(())
+    }
+
+    /// Runs the next node and pauses again before the one after it.
+    pub fn step(&self) -> bool {
+        self.resume(Resume::Step)
+    }
+
+    /// Runs until the next breakpoint.
+    pub fn continue_run(&self) -> bool {
+        self.resume(Resume::Continue)
+    }
+
+    fn resume(&self, how: Resume) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.paused.is_none() {
+            return false;
+        }
+        state.resume = Some(how);
+        self.notify.notify_one();
+        true
+    }
+
+    pub(crate) fn should_pause(&self, node: &Node) -> bool {
+        self.state.lock().unwrap().stepping || node.data.get("breakpoint").and_then(|v| v.as_bool()).unwrap_or(false)
+    }
+
+    /// Blocks the run in front of `node` until it is resumed or cancelled, then
+    /// hands back any variable edits made in the meantime.
+    pub(crate) async fn pause(&self, node: &Node, variables: &mut HashMap<String, serde_json::Value>, results: &HashMap<String, ExecutionResult>, cancel: &CancellationToken) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.resume = None;
+            state.paused = Some(PausedState { node_id: node.id.clone(), variables: variables.clone(), results: results.clone() });
+        }
+
+        loop {
+            let notified = self.notify.notified();
+            if self.take_resume(variables) {
+                return;
+            }
+
+            tokio::select! {
+                _ = notified => {},
+                _ = cancel.cancelled() => {
+                    self.state.lock().unwrap().paused = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Leaves the paused state if a resume was requested, copying edited variables back.
+    fn take_resume(&self, variables: &mut HashMap<String, serde_json::Value>) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let Some(how) = state.resume.take() else {
+            return false;
+        };
+        state.stepping = how == Resume::Step;
+        if let Some(paused) = state.paused.take() {
+            *variables = paused.variables;
+        }
+        true
+    }
+}
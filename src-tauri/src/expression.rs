@@ -0,0 +1,796 @@
+//! A small expression language for branching and checks, e.g.
+//! `status == 200 && data.items.len() > 0 && vars.retries < 3`.
+//!
+//! Expressions are parsed and evaluated here and can't reach anything but the
+//! values they are given: no I/O, no assignments, no loops.
+//!
+//! - Literals: `200`, `1.5`, `'text'`, `"text"`, `true`, `false`, `null`, `[1, 2]`
+//! - Names: the fields of the input (`status`, `data`), plus the bindings the
+//!   caller adds, such as `vars`, `input` or `nodes`; bindings win over fields
+//! - Access: `data.items[0].name`, `headers['content-type']`; missing fields are `null`
+//! - Operators: `+ - * / %`, `== != < <= > >=`, `=~` (regex), `in`, `&& || !`,
+//!   `cond ? a : b` and parentheses. `+` also joins strings and arrays
+//! - Methods: `len()`, `isEmpty()`, `contains(x)`, `startsWith(s)`, `endsWith(s)`,
+//!   `lower()`, `upper()`, `trim()`, `split(s)`, `keys()`, `toNumber()`, `toString()`
+//!
+//! `null`, `false`, `0`, `""`, `[]` and `{}` count as false, everything else as true.
+
+use regex::Regex;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// How deeply expressions may nest, so a hostile one can't exhaust the stack
+/// while it is parsed or evaluated. Every operator, access and call wraps the
+/// expression before it, so `a + b + c` is as deep as `(a + b) + c`.
+const MAX_DEPTH: usize = 64;
+
+/// A parsed expression, ready to be evaluated any number of times.
+#[derive(Debug, Clone)]
+pub struct Expression {
+    root: Expr,
+}
+
+/// The values an expression is evaluated against.
+pub struct Env<'a> {
+    fields: &'a Value,
+    bindings: HashMap<&'a str, &'a Value>,
+}
+
+impl<'a> Env<'a> {
+    /// An environment whose bare names are the fields of `fields`.
+    pub fn new(fields: &'a Value) -> Self {
+        Self { fields, bindings: HashMap::new() }
+    }
+
+    pub fn bind(mut self, name: &'a str, value: &'a Value) -> Self {
+        self.bindings.insert(name, value);
+        self
+    }
+
+    fn lookup(&self, name: &str) -> Value {
+        match self.bindings.get(name) {
+            Some(value) => (*value).clone(),
+            None => self.fields.get(name).cloned().unwrap_or(Value::Null),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Name(String),
+    Array(Vec<Expr>),
+    Member(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, String, Vec<Expr>),
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Matches,
+    In,
+}
+
+impl Expression {
+    pub fn parse(text: &str) -> Result<Expression, String> {
+        let tokens = tokenize(text)?;
+        if tokens.is_empty() {
+            return Err("the expression is empty".to_string());
+        }
+        let mut parser = Parser { tokens, pos: 0, depth: 0 };
+        let root = parser.conditional()?;
+        match parser.tokens.get(parser.pos) {
+            None => Ok(Expression { root }),
+            Some(token) => Err(format!("unexpected {}", token)),
+        }
+    }
+
+    /// Whether the expression uses the name `name` (as a field or binding), so
+    /// callers only build the bindings an expression actually needs.
+    pub fn references(&self, name: &str) -> bool {
+        self.root.references(name)
+    }
+
+    pub fn evaluate(&self, env: &Env) -> Result<Value, String> {
+        self.root.evaluate(env)
+    }
+
+    /// Evaluates the expression and reports whether the result counts as true.
+    pub fn test(&self, env: &Env) -> Result<bool, String> {
+        Ok(truthy(&self.evaluate(env)?))
+    }
+}
+
+/// Whether `value` counts as true.
+pub fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+impl Expr {
+    fn references(&self, name: &str) -> bool {
+        match self {
+            Expr::Literal(_) => false,
+            Expr::Name(n) => n == name,
+            Expr::Array(items) => items.iter().any(|e| e.references(name)),
+            Expr::Member(e, _) | Expr::Not(e) | Expr::Negate(e) => e.references(name),
+            Expr::Call(e, _, args) => e.references(name) || args.iter().any(|a| a.references(name)),
+            Expr::Index(a, b) | Expr::And(a, b) | Expr::Or(a, b) | Expr::Binary(a, _, b) => a.references(name) || b.references(name),
+            Expr::Conditional(c, a, b) => c.references(name) || a.references(name) || b.references(name),
+        }
+    }
+
+    fn evaluate(&self, env: &Env) -> Result<Value, String> {
+        Ok(match self {
+            Expr::Literal(value) => value.clone(),
+            Expr::Name(name) => env.lookup(name),
+            Expr::Array(items) => Value::Array(items.iter().map(|e| e.evaluate(env)).collect::<Result<_, _>>()?),
+            Expr::Member(target, key) => member(&target.evaluate(env)?, key),
+            Expr::Index(target, index) => {
+                let target = target.evaluate(env)?;
+                match index.evaluate(env)? {
+                    Value::String(key) => member(&target, &key),
+                    Value::Number(n) => match (&target, n.as_i64()) {
+                        (Value::Array(items), Some(i)) => {
+                            let i = if i < 0 { items.len().checked_sub(i.unsigned_abs() as usize) } else { Some(i as usize) };
+                            i.and_then(|i| items.get(i)).cloned().unwrap_or(Value::Null)
+                        }
+                        _ => Value::Null,
+                    },
+                    other => return Err(format!("can't index with {}", kind(&other))),
+                }
+            }
+            Expr::Call(target, method, args) => {
+                let target = target.evaluate(env)?;
+                let args = args.iter().map(|a| a.evaluate(env)).collect::<Result<Vec<_>, _>>()?;
+                call(&target, method, &args)?
+            }
+            Expr::Not(e) => Value::Bool(!truthy(&e.evaluate(env)?)),
+            Expr::Negate(e) => match e.evaluate(env)? {
+                Value::Number(n) => number(-n.as_f64().unwrap_or_default(), n.as_i64().and_then(|i| i.checked_neg())),
+                other => return Err(format!("can't negate {}", kind(&other))),
+            },
+            Expr::And(a, b) => Value::Bool(truthy(&a.evaluate(env)?) && truthy(&b.evaluate(env)?)),
+            Expr::Or(a, b) => Value::Bool(truthy(&a.evaluate(env)?) || truthy(&b.evaluate(env)?)),
+            Expr::Binary(a, op, b) => binary(&a.evaluate(env)?, *op, &b.evaluate(env)?)?,
+            Expr::Conditional(c, a, b) => {
+                if truthy(&c.evaluate(env)?) {
+                    a.evaluate(env)?
+                } else {
+                    b.evaluate(env)?
+                }
+            }
+        })
+    }
+}
+
+fn member(target: &Value, key: &str) -> Value {
+    match target {
+        Value::Object(map) => map.get(key).cloned().unwrap_or(Value::Null),
+        Value::Array(items) => key.parse::<usize>().ok().and_then(|i| items.get(i)).cloned().unwrap_or(Value::Null),
+        _ => Value::Null,
+    }
+}
+
+/// A number result, kept an integer when the operation had an exact integer answer.
+fn number(float: f64, int: Option<i64>) -> Value {
+    match int {
+        Some(i) => Value::from(i),
+        None => serde_json::Number::from_f64(float).map_or(Value::Null, Value::Number),
+    }
+}
+
+fn kind(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Equality that treats `1` and `1.0` as the same number.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => a == b,
+    }
+}
+
+fn binary(a: &Value, op: BinaryOp, b: &Value) -> Result<Value, String> {
+    let mismatch = || format!("can't apply '{}' to {} and {}", op.symbol(), kind(a), kind(b));
+    Ok(match op {
+        BinaryOp::Eq => Value::Bool(equal(a, b)),
+        BinaryOp::Ne => Value::Bool(!equal(a, b)),
+        BinaryOp::Lt | BinaryOp::Le | BinaryOp::Gt | BinaryOp::Ge => {
+            let ordering = match (a, b) {
+                (Value::Number(x), Value::Number(y)) => x.as_f64().partial_cmp(&y.as_f64()),
+                (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+                _ => return Err(mismatch()),
+            };
+            Value::Bool(match op {
+                BinaryOp::Lt => ordering == Some(Ordering::Less),
+                BinaryOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                BinaryOp::Gt => ordering == Some(Ordering::Greater),
+                _ => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            })
+        }
+        BinaryOp::Matches => {
+            let (Value::String(subject), Value::String(pattern)) = (a, b) else { return Err(mismatch()) };
+            let regex = Regex::new(pattern).map_err(|e| format!("invalid pattern '{}': {}", pattern, e))?;
+            Value::Bool(regex.is_match(subject))
+        }
+        BinaryOp::In => Value::Bool(match b {
+            Value::Array(items) => items.iter().any(|item| equal(item, a)),
+            Value::Object(map) => a.as_str().is_some_and(|key| map.contains_key(key)),
+            Value::String(haystack) => a.as_str().is_some_and(|needle| haystack.contains(needle)),
+            _ => return Err(mismatch()),
+        }),
+        BinaryOp::Add => match (a, b) {
+            (Value::Number(x), Value::Number(y)) => number(
+                x.as_f64().unwrap_or_default() + y.as_f64().unwrap_or_default(),
+                x.as_i64().zip(y.as_i64()).and_then(|(x, y)| x.checked_add(y)),
+            ),
+            (Value::Array(x), Value::Array(y)) => Value::Array(x.iter().chain(y).cloned().collect()),
+            (Value::String(_), _) | (_, Value::String(_)) => Value::String(text(a) + &text(b)),
+            _ => return Err(mismatch()),
+        },
+        BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+            let (Value::Number(x), Value::Number(y)) = (a, b) else { return Err(mismatch()) };
+            let (fx, fy) = (x.as_f64().unwrap_or_default(), y.as_f64().unwrap_or_default());
+            let ints = x.as_i64().zip(y.as_i64());
+            if matches!(op, BinaryOp::Div | BinaryOp::Rem) && fy == 0.0 {
+                return Err("division by zero".to_string());
+            }
+            match op {
+                BinaryOp::Sub => number(fx - fy, ints.and_then(|(x, y)| x.checked_sub(y))),
+                BinaryOp::Mul => number(fx * fy, ints.and_then(|(x, y)| x.checked_mul(y))),
+                BinaryOp::Div => number(fx / fy, ints.filter(|(x, y)| x.checked_rem(*y) == Some(0)).and_then(|(x, y)| x.checked_div(y))),
+                _ => number(fx % fy, ints.and_then(|(x, y)| x.checked_rem(y))),
+            }
+        }
+    })
+}
+
+fn call(target: &Value, method: &str, args: &[Value]) -> Result<Value, String> {
+    let arity = |n: usize| if args.len() == n { Ok(()) } else { Err(format!("{}() takes {} argument(s)", method, n)) };
+    let string_arg = || args[0].as_str().ok_or_else(|| format!("{}() needs a string argument", method));
+    let unsupported = || format!("{}() doesn't work on {}", method, kind(target));
+    Ok(match method {
+        "len" | "isEmpty" => {
+            arity(0)?;
+            let len = match target {
+                Value::String(s) => s.chars().count(),
+                Value::Array(items) => items.len(),
+                Value::Object(map) => map.len(),
+                _ => return Err(unsupported()),
+            };
+            if method == "len" { Value::from(len) } else { Value::Bool(len == 0) }
+        }
+        "contains" => {
+            arity(1)?;
+            match target {
+                Value::Array(items) => Value::Bool(items.iter().any(|item| equal(item, &args[0]))),
+                Value::Object(map) => Value::Bool(map.contains_key(string_arg()?)),
+                Value::String(s) => Value::Bool(s.contains(string_arg()?)),
+                _ => return Err(unsupported()),
+            }
+        }
+        "startsWith" | "endsWith" | "split" => {
+            arity(1)?;
+            let (Value::String(s), arg) = (target, string_arg()?) else { return Err(unsupported()) };
+            match method {
+                "startsWith" => Value::Bool(s.starts_with(arg)),
+                "endsWith" => Value::Bool(s.ends_with(arg)),
+                _ => Value::Array(s.split(arg).map(|part| Value::String(part.to_string())).collect()),
+            }
+        }
+        "lower" | "upper" | "trim" => {
+            arity(0)?;
+            let Value::String(s) = target else { return Err(unsupported()) };
+            Value::String(match method {
+                "lower" => s.to_lowercase(),
+                "upper" => s.to_uppercase(),
+                _ => s.trim().to_string(),
+            })
+        }
+        "keys" => {
+            arity(0)?;
+            let Value::Object(map) = target else { return Err(unsupported()) };
+            Value::Array(map.keys().map(|k| Value::String(k.clone())).collect())
+        }
+        "toNumber" => {
+            arity(0)?;
+            match target {
+                Value::Number(_) => target.clone(),
+                Value::String(s) => serde_json::from_str::<serde_json::Number>(s.trim()).map(Value::Number).unwrap_or(Value::Null),
+                Value::Bool(b) => Value::from(*b as i64),
+                _ => Value::Null,
+            }
+        }
+        "toString" => {
+            arity(0)?;
+            Value::String(text(target))
+        }
+        _ => return Err(format!("unknown method '{}'", method)),
+    })
+}
+
+impl BinaryOp {
+    fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Le => "<=",
+            BinaryOp::Gt => ">",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Matches => "=~",
+            BinaryOp::In => "in",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(serde_json::Number),
+    Str(String),
+    Ident(String),
+    /// Operators and punctuation.
+    Symbol(&'static str),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "'{}'", n),
+            Token::Str(s) => write!(f, "'{}'", s),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Symbol(symbol) => write!(f, "'{}'", symbol),
+        }
+    }
+}
+
+/// Longest first, so `<=` isn't read as `<` followed by `=`.
+const SYMBOLS: &[&str] = &[
+    "==", "!=", "<=", ">=", "=~", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "(", ")", "[", "]", ".", ",", "?", ":",
+];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.' && chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())) {
+                i += 1;
+            }
+            let literal: String = chars[start..i].iter().collect();
+            tokens.push(Token::Number(serde_json::from_str(&literal).map_err(|_| format!("invalid number '{}'", literal))?));
+        } else if c == '\'' || c == '"' {
+            let mut value = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated string".to_string()),
+                    Some(&q) if q == c => break,
+                    Some('\\') => {
+                        i += 1;
+                        match chars.get(i) {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(&other) => value.push(other),
+                            None => return Err("unterminated string".to_string()),
+                        }
+                    }
+                    Some(&other) => value.push(other),
+                }
+                i += 1;
+            }
+            i += 1;
+            tokens.push(Token::Str(value));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else {
+            let symbol = SYMBOLS.iter()
+                .find(|symbol| symbol.chars().enumerate().all(|(j, s)| chars.get(i + j) == Some(&s)))
+                .ok_or_else(|| format!("unexpected '{}'", c))?;
+            i += symbol.len();
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn eat(&mut self, symbol: &str) -> bool {
+        let matched = matches!(self.tokens.get(self.pos), Some(Token::Symbol(s)) if *s == symbol);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn eat_word(&mut self, word: &str) -> bool {
+        let matched = matches!(self.tokens.get(self.pos), Some(Token::Ident(w)) if w == word);
+        if matched {
+            self.pos += 1;
+        }
+        matched
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.tokens.get(self.pos) {
+            Some(token) => Err(format!("expected '{}' but found {}", symbol, token)),
+            None => Err(format!("expected '{}' at the end", symbol)),
+        }
+    }
+
+    /// Counts one more level of nesting.
+    fn deepen(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("the expression nests deeper than {} levels", MAX_DEPTH));
+        }
+        Ok(())
+    }
+
+    fn conditional(&mut self) -> Result<Expr, String> {
+        self.deepen()?;
+        let condition = self.or()?;
+        let expr = if self.eat("?") {
+            let then = self.conditional()?;
+            self.expect(":")?;
+            Expr::Conditional(Box::new(condition), Box::new(then), Box::new(self.conditional()?))
+        } else {
+            condition
+        };
+        self.depth -= 1;
+        Ok(expr)
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.and()?;
+        while self.eat("||") {
+            self.deepen()?;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.comparison()?;
+        while self.eat("&&") {
+            self.deepen()?;
+            expr = Expr::And(Box::new(expr), Box::new(self.comparison()?));
+        }
+        self.depth = depth;
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.additive()?;
+        let ops = [("==", BinaryOp::Eq), ("!=", BinaryOp::Ne), ("<=", BinaryOp::Le), (">=", BinaryOp::Ge), ("<", BinaryOp::Lt), (">", BinaryOp::Gt), ("=~", BinaryOp::Matches)];
+        let op = match ops.into_iter().find(|(symbol, _)| self.eat(symbol)) {
+            Some((_, op)) => op,
+            None if self.eat_word("in") => BinaryOp::In,
+            None => return Ok(left),
+        };
+        let right = self.nested(Self::additive)?;
+        Ok(Expr::Binary(Box::new(left), op, Box::new(right)))
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.multiplicative()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Sub
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            };
+            self.deepen()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.multiplicative()?));
+        }
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                BinaryOp::Mul
+            } else if self.eat("/") {
+                BinaryOp::Div
+            } else if self.eat("%") {
+                BinaryOp::Rem
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            };
+            self.deepen()?;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat("!") {
+            return Ok(Expr::Not(Box::new(self.nested(Self::unary)?)));
+        }
+        if self.eat("-") {
+            return Ok(Expr::Negate(Box::new(self.nested(Self::unary)?)));
+        }
+        self.postfix()
+    }
+
+    /// Runs `parse` one nesting level deeper.
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Expr, String>) -> Result<Expr, String> {
+        self.deepen()?;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.primary()?;
+        loop {
+            if self.eat(".") {
+                self.deepen()?;
+                let name = match self.tokens.get(self.pos) {
+                    Some(Token::Ident(name)) => name.clone(),
+                    Some(Token::Number(n)) if n.is_u64() => n.to_string(),
+                    Some(token) => return Err(format!("expected a field name after '.' but found {}", token)),
+                    None => return Err("expected a field name after '.'".to_string()),
+                };
+                self.pos += 1;
+                expr = if self.eat("(") {
+                    Expr::Call(Box::new(expr), name, self.list(")")?)
+                } else {
+                    Expr::Member(Box::new(expr), name)
+                };
+            } else if self.eat("[") {
+                self.deepen()?;
+                let index = self.conditional()?;
+                self.expect("]")?;
+                expr = Expr::Index(Box::new(expr), Box::new(index));
+            } else {
+                self.depth = depth;
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// Comma-separated expressions up to `close`, which is consumed.
+    fn list(&mut self, close: &str) -> Result<Vec<Expr>, String> {
+        let mut items = Vec::new();
+        if self.eat(close) {
+            return Ok(items);
+        }
+        loop {
+            items.push(self.conditional()?);
+            if self.eat(close) {
+                return Ok(items);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            return Err("the expression ends too early".to_string());
+        };
+        self.pos += 1;
+        Ok(match token {
+            Token::Number(n) => Expr::Literal(Value::Number(n)),
+            Token::Str(s) => Expr::Literal(Value::String(s)),
+            Token::Ident(word) => match word.as_str() {
+                "true" => Expr::Literal(Value::Bool(true)),
+                "false" => Expr::Literal(Value::Bool(false)),
+                "null" => Expr::Literal(Value::Null),
+                _ => Expr::Name(word),
+            },
+            Token::Symbol("(") => {
+                let expr = self.conditional()?;
+                self.expect(")")?;
+                expr
+            }
+            Token::Symbol("[") => Expr::Array(self.list("]")?),
+            other => return Err(format!("unexpected {}", other)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn eval(text: &str, fields: Value) -> Result<Value, String> {
+        Expression::parse(text)?.evaluate(&Env::new(&fields))
+    }
+
+    fn value(text: &str) -> Value {
+        eval(text, json!({})).unwrap()
+    }
+
+    #[test]
+    fn respects_operator_precedence() {
+        assert_eq!(value("1 + 2 * 3"), json!(7));
+        assert_eq!(value("(1 + 2) * 3"), json!(9));
+        assert_eq!(value("10 - 4 - 3"), json!(3));
+        assert_eq!(value("-2 * 3"), json!(-6));
+        assert_eq!(value("1 + 1 == 2 && 3 > 2"), json!(true));
+        assert_eq!(value("false && true || true"), json!(true));
+        assert_eq!(value("false && (true || true)"), json!(false));
+        assert_eq!(value("!false && false"), json!(false));
+        assert_eq!(value("1 > 2 ? 'a' : 2 > 1 ? 'b' : 'c'"), json!("b"));
+    }
+
+    #[test]
+    fn keeps_integers_exact_and_falls_back_to_floats() {
+        assert_eq!(value("6 / 3"), json!(2));
+        assert_eq!(value("7 / 2"), json!(3.5));
+        assert_eq!(value("7 % 4"), json!(3));
+        assert_eq!(value("1.5 + 1"), json!(2.5));
+        assert_eq!(value("9223372036854775807 + 1"), json!(9223372036854775808.0));
+        assert_eq!(eval("1 / 0", json!({})), Err("division by zero".to_string()));
+    }
+
+    #[test]
+    fn integer_overflow_does_not_panic() {
+        let min = json!({ "min": i64::MIN });
+        assert_eq!(eval("min / -1", min.clone()).unwrap(), json!(9223372036854775808.0));
+        assert_eq!(eval("min % -1", min.clone()).unwrap(), json!(-0.0));
+        assert_eq!(eval("-min", min.clone()).unwrap(), json!(9223372036854775808.0));
+        assert_eq!(eval("min * 2", min.clone()).unwrap(), json!(-18446744073709551616.0));
+        assert_eq!(eval("min - 1", min).unwrap(), json!(-9223372036854775808.0));
+    }
+
+    #[test]
+    fn reads_fields_bindings_and_paths() {
+        let fields = json!({ "status": 200, "data": { "items": [1, 2, 3], "name": "Ada" }, "headers": { "content-type": "text/html" } });
+        let vars = json!({ "retries": 1 });
+        let env = Env::new(&fields).bind("vars", &vars);
+        let test = |text: &str| Expression::parse(text).unwrap().evaluate(&env).unwrap();
+
+        assert_eq!(test("status == 200 && data.items.len() > 0 && vars.retries < 3"), json!(true));
+        assert_eq!(test("data.items[-1]"), json!(3));
+        assert_eq!(test("data.items.1"), json!(2));
+        assert_eq!(test("headers['content-type']"), json!("text/html"));
+        assert_eq!(test("data.missing.deeper"), json!(null));
+        assert_eq!(test("2 in data.items && 'name' in data && 'Ad' in data.name"), json!(true));
+        assert_eq!(test("data.name =~ '^A'"), json!(true));
+        assert_eq!(test("data.name.lower() + '!'"), json!("ada!"));
+        assert_eq!(test("'a,b'.split(',')"), json!(["a", "b"]));
+        assert_eq!(test("'12'.toNumber() + 1"), json!(13));
+        assert_eq!(test("[1, 2] + [3]"), json!([1, 2, 3]));
+    }
+
+    #[test]
+    fn bindings_win_over_fields() {
+        let fields = json!({ "input": "field" });
+        let input = json!("binding");
+        let expression = Expression::parse("input").unwrap();
+        assert_eq!(expression.evaluate(&Env::new(&fields).bind("input", &input)).unwrap(), json!("binding"));
+        assert!(expression.references("input"));
+        assert!(!expression.references("vars"));
+    }
+
+    #[test]
+    fn follows_truthiness() {
+        for falsy in ["null", "false", "0", "''", "[]"] {
+            assert!(!Expression::parse(falsy).unwrap().test(&Env::new(&json!({}))).unwrap(), "{}", falsy);
+        }
+        assert!(!truthy(&json!({})));
+        for truthy in ["1", "'0'", "[0]", "true", "-1"] {
+            assert!(Expression::parse(truthy).unwrap().test(&Env::new(&json!({}))).unwrap(), "{}", truthy);
+        }
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let error = |text: &str| Expression::parse(text).unwrap_err();
+        assert_eq!(error(""), "the expression is empty");
+        assert_eq!(error("1 +"), "the expression ends too early");
+        assert_eq!(error("(1"), "expected ')' at the end");
+        assert_eq!(error("1 2"), "unexpected '2'");
+        assert_eq!(error("'abc"), "unterminated string");
+        assert_eq!(error("a # b"), "unexpected '#'");
+        assert_eq!(error("a."), "expected a field name after '.'");
+        assert_eq!(error("[1 2]"), "expected ',' but found '2'");
+    }
+
+    #[test]
+    fn reports_evaluation_errors() {
+        assert_eq!(eval("'a' - 1", json!({})), Err("can't apply '-' to a string and a number".to_string()));
+        assert_eq!(eval("1 < 'a'", json!({})), Err("can't apply '<' to a number and a string".to_string()));
+        assert_eq!(eval("-'a'", json!({})), Err("can't negate a string".to_string()));
+        assert_eq!(eval("'a'.nope()", json!({})), Err("unknown method 'nope'".to_string()));
+        assert_eq!(eval("'a'.len(1)", json!({})), Err("len() takes 0 argument(s)".to_string()));
+        assert_eq!(eval("1.len()", json!({})), Err("len() doesn't work on a number".to_string()));
+        assert!(eval("'a' =~ '('", json!({})).unwrap_err().starts_with("invalid pattern '('"));
+    }
+
+    #[test]
+    fn limits_nesting_depth() {
+        let too_deep = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+        assert_eq!(Expression::parse(&too_deep).unwrap_err(), "the expression nests deeper than 64 levels");
+        assert!(Expression::parse(&format!("{}1{}", "(".repeat(10), ")".repeat(10))).is_ok());
+        assert!(Expression::parse(&"!".repeat(100_000)).is_err());
+        assert!(Expression::parse(&format!("{}1{}", "[".repeat(100_000), "]".repeat(100_000))).is_err());
+    }
+
+    #[test]
+    fn limits_long_operator_chains() {
+        let long = |op: &str| format!("1{}", format!("{}1", op).repeat(20_000));
+        for op in [" + ", " * ", " && ", " || "] {
+            assert_eq!(Expression::parse(&long(op)).unwrap_err(), "the expression nests deeper than 64 levels");
+        }
+        assert!(Expression::parse(&format!("a{}", ".b".repeat(20_000))).is_err());
+        assert!(Expression::parse(&format!("a{}", "[0]".repeat(20_000))).is_err());
+        assert!(Expression::parse(&format!("a{}", ".len()".repeat(20_000))).is_err());
+        assert_eq!(value(&format!("1{}", " + 1".repeat(39))), json!(40));
+    }
+}
@@ -0,0 +1,648 @@
+//! JSONPath queries for nodes that pick values out of their input (`capture`,
+//! `arrayMap`, `filter`) and for the canvas' value picker.
+//!
+//! Supported syntax:
+//!
+//! - `$.data.users[0].name`, `$['content-type']`, `$.items[-1]`
+//! - `data.users.0.name`: the leading `$` is optional, and a numeric key also
+//!   indexes an array, so the dot paths these nodes always took keep working
+//!   (see [`JsonPath::parse_lenient`] for the ones that aren't JSONPath)
+//! - `$.users[*].name` (wildcard), `$..name` (any depth)
+//! - `$.items[1:3]`, `$.items[::2]` (slices), `$.items[0,2]`, `$['a','b']` (unions)
+//! - `$.users[?(@.age >= 18 && @.active)]`, `[?(@.email =~ '.*@example\.com')]`
+//!   (filters with `== != < <= > >= =~`, `&& || !`, parentheses, and `$` for the
+//!   document root; a bare `@.key` holds when the key is there and isn't
+//!   `false` or `null`)
+//!
+//! A path that names one value (only keys and indexes) evaluates to that value,
+//! or `null` when it's missing. Any other path evaluates to the array of every
+//! match, which may be empty.
+
+use regex::Regex;
+use serde_json::Value;
+use std::cmp::Ordering;
+
+/// How deeply filters may nest, so a hostile path can't exhaust the stack.
+/// Every `&&`, `||`, `!`, parenthesis and nested filter counts as a level.
+const MAX_DEPTH: usize = 64;
+
+/// A parsed JSONPath expression.
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Child(Selector),
+    /// `..`: the selector applied to the value and everything below it.
+    Descendant(Selector),
+}
+
+#[derive(Debug, Clone)]
+enum Selector {
+    Name(String),
+    Index(i64),
+    Wildcard,
+    Slice(Option<i64>, Option<i64>, i64),
+    Union(Vec<Selector>),
+    Filter(Expr),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    /// A path from the current item (`@`) or the document root (`$`).
+    Path { root: bool, path: JsonPath },
+    Literal(Value),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Box<Expr>, CompareOp, Box<Expr>),
+    Matches(Box<Expr>, Regex),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<JsonPath, String> {
+        let mut parser = Parser { chars: path.trim().chars().collect(), pos: 0, depth: 0 };
+        let path = parser.path(true)?;
+        match parser.peek() {
+            None => Ok(path),
+            Some(c) => Err(format!("unexpected '{}' at position {}", c, parser.pos)),
+        }
+    }
+
+    /// Like [`JsonPath::parse`], but a path without a leading `$` that doesn't
+    /// parse is read the way nodes read paths before JSONPath: keys split on
+    /// dots, so `data.user name` is the key `user name` inside `data`.
+    pub fn parse_lenient(path: &str) -> Result<JsonPath, String> {
+        match JsonPath::parse(path) {
+            Err(_) if !path.trim_start().starts_with('$') => Ok(JsonPath {
+                segments: path.split('.').map(|key| Segment::Child(Selector::Name(key.to_string()))).collect(),
+            }),
+            parsed => parsed,
+        }
+    }
+
+    /// Whether the path can name at most one value.
+    pub fn is_definite(&self) -> bool {
+        self.segments.iter().all(|s| matches!(s, Segment::Child(Selector::Name(_) | Selector::Index(_))))
+    }
+
+    /// Every value the path matches, in document order.
+    pub fn select<'v>(&self, value: &'v Value) -> Vec<&'v Value> {
+        self.select_from(value, value)
+    }
+
+    /// The value for a definite path (`null` when missing), otherwise the array of matches.
+    pub fn query(&self, value: &Value) -> Value {
+        let matches = self.select(value);
+        if self.is_definite() {
+            matches.first().map_or(Value::Null, |v| (*v).clone())
+        } else {
+            Value::Array(matches.into_iter().cloned().collect())
+        }
+    }
+
+    /// Like [`JsonPath::query`], but `None` when nothing matches.
+    pub fn find(&self, value: &Value) -> Option<Value> {
+        let matches = self.select(value);
+        match (self.is_definite(), matches.is_empty()) {
+            (_, true) => None,
+            (true, false) => Some(matches[0].clone()),
+            (false, false) => Some(Value::Array(matches.into_iter().cloned().collect())),
+        }
+    }
+
+    fn select_from<'v>(&self, value: &'v Value, root: &'v Value) -> Vec<&'v Value> {
+        let mut current = vec![value];
+        for segment in &self.segments {
+            let mut next = Vec::new();
+            for value in current {
+                match segment {
+                    Segment::Child(selector) => selector.apply(value, root, &mut next),
+                    Segment::Descendant(selector) => {
+                        let mut stack = vec![value];
+                        while let Some(value) = stack.pop() {
+                            selector.apply(value, root, &mut next);
+                            match value {
+                                Value::Array(items) => stack.extend(items.iter().rev()),
+                                Value::Object(map) => stack.extend(map.values().rev()),
+                                _ => {}
+                            }
+                        }
+                    }
+                }
+            }
+            current = next;
+        }
+        current
+    }
+}
+
+/// Evaluates `path` against `value`; see [`JsonPath::query`].
+pub fn query(value: &Value, path: &str) -> Result<Value, String> {
+    Ok(JsonPath::parse(path)?.query(value))
+}
+
+impl Selector {
+    fn apply<'v>(&self, value: &'v Value, root: &'v Value, out: &mut Vec<&'v Value>) {
+        match (self, value) {
+            (Selector::Name(name), Value::Object(map)) => out.extend(map.get(name)),
+            // `items.0` indexes arrays, as the old dot paths did.
+            (Selector::Name(name), Value::Array(items)) => out.extend(name.parse::<usize>().ok().and_then(|i| items.get(i))),
+            (Selector::Index(index), Value::Array(items)) => {
+                let index = if *index < 0 { items.len().checked_sub(index.unsigned_abs() as usize) } else { Some(*index as usize) };
+                out.extend(index.and_then(|i| items.get(i)));
+            }
+            (Selector::Wildcard, Value::Array(items)) => out.extend(items),
+            (Selector::Wildcard, Value::Object(map)) => out.extend(map.values()),
+            (Selector::Slice(start, end, step), Value::Array(items)) => {
+                let len = items.len() as i64;
+                let bound = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) };
+                // A step past the end of the range stops there.
+                if *step > 0 {
+                    let (mut i, end) = (bound(start.unwrap_or(0)), bound(end.unwrap_or(len)));
+                    while i < end {
+                        out.push(&items[i as usize]);
+                        let Some(next) = i.checked_add(*step) else { break };
+                        i = next;
+                    }
+                } else if *step < 0 {
+                    let mut i = start.map_or(len - 1, |s| bound(s).min(len - 1));
+                    let end = end.map_or(-1, |e| if e < 0 { (len + e).max(-1) } else { e.min(len) });
+                    while i > end {
+                        out.push(&items[i as usize]);
+                        let Some(next) = i.checked_add(*step) else { break };
+                        i = next;
+                    }
+                }
+            }
+            (Selector::Union(selectors), _) => {
+                for selector in selectors {
+                    selector.apply(value, root, out);
+                }
+            }
+            (Selector::Filter(expr), Value::Array(items)) => out.extend(items.iter().filter(|item| expr.test(item, root))),
+            (Selector::Filter(expr), Value::Object(map)) => out.extend(map.values().filter(|item| expr.test(item, root))),
+            _ => {}
+        }
+    }
+}
+
+impl Expr {
+    fn test(&self, current: &Value, root: &Value) -> bool {
+        match self {
+            Expr::Path { .. } | Expr::Literal(_) => self.operand(current, root).is_some_and(|v| !matches!(v, Value::Null | Value::Bool(false))),
+            Expr::Not(expr) => !expr.test(current, root),
+            Expr::And(a, b) => a.test(current, root) && b.test(current, root),
+            Expr::Or(a, b) => a.test(current, root) || b.test(current, root),
+            Expr::Compare(a, op, b) => {
+                let (a, b) = (a.operand(current, root), b.operand(current, root));
+                match op {
+                    CompareOp::Eq => a == b,
+                    CompareOp::Ne => a != b,
+                    CompareOp::Lt => compare(a, b) == Some(Ordering::Less),
+                    CompareOp::Le => matches!(compare(a, b), Some(Ordering::Less | Ordering::Equal)),
+                    CompareOp::Gt => compare(a, b) == Some(Ordering::Greater),
+                    CompareOp::Ge => matches!(compare(a, b), Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+            Expr::Matches(expr, regex) => expr.operand(current, root).is_some_and(|v| v.as_str().is_some_and(|s| regex.is_match(s))),
+        }
+    }
+
+    /// The value an operand stands for; `None` for a path that matches nothing.
+    fn operand<'v>(&'v self, current: &'v Value, root: &'v Value) -> Option<&'v Value> {
+        match self {
+            Expr::Path { root: true, path } => path.select_from(root, root).into_iter().next(),
+            Expr::Path { root: false, path } => path.select_from(current, root).into_iter().next(),
+            Expr::Literal(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+fn compare(a: Option<&Value>, b: Option<&Value>) -> Option<Ordering> {
+    match (a?, b?) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64()?.partial_cmp(&b.as_f64()?),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn eat(&mut self, expected: &str) -> bool {
+        let matched = expected.chars().enumerate().all(|(i, c)| self.peek_at(i) == Some(c));
+        if matched {
+            self.pos += expected.chars().count();
+        }
+        matched
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(format!("expected '{}' at position {}", expected, self.pos))
+        }
+    }
+
+    /// Counts one more level of nesting.
+    fn deepen(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return Err(format!("the path nests deeper than {} levels", MAX_DEPTH));
+        }
+        Ok(())
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// A path after its root. At the top level the `$` is optional and the
+    /// path may start with a bare key.
+    fn path(&mut self, top_level: bool) -> Result<JsonPath, String> {
+        let mut segments = Vec::new();
+        if top_level && !self.eat("$") && self.peek().is_some_and(|c| c != '.' && c != '[') {
+            segments.push(Segment::Child(Selector::Name(self.name()?)));
+        }
+        loop {
+            if self.eat("..") {
+                let selector = match self.peek() {
+                    Some('[') => self.bracket()?,
+                    Some('*') => {
+                        self.pos += 1;
+                        Selector::Wildcard
+                    }
+                    _ => Selector::Name(self.name()?),
+                };
+                segments.push(Segment::Descendant(selector));
+            } else if self.eat(".") {
+                let selector = if self.eat("*") { Selector::Wildcard } else { Selector::Name(self.name()?) };
+                segments.push(Segment::Child(selector));
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracket()?));
+            } else {
+                return Ok(JsonPath { segments });
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && !".[]()=!<>&|,'\"".contains(c)) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(format!("expected a key at position {}", start));
+        }
+        Ok(self.chars[start..self.pos].iter().collect())
+    }
+
+    fn bracket(&mut self) -> Result<Selector, String> {
+        self.expect("[")?;
+        self.skip_spaces();
+        if self.eat("?") {
+            self.deepen()?;
+            self.skip_spaces();
+            let expr = self.or()?;
+            self.skip_spaces();
+            self.expect("]")?;
+            self.depth -= 1;
+            return Ok(Selector::Filter(expr));
+        }
+        let mut selectors = vec![self.bracket_item()?];
+        loop {
+            self.skip_spaces();
+            if self.eat("]") {
+                break;
+            }
+            self.expect(",")?;
+            self.skip_spaces();
+            selectors.push(self.bracket_item()?);
+        }
+        Ok(if selectors.len() == 1 { selectors.remove(0) } else { Selector::Union(selectors) })
+    }
+
+    fn bracket_item(&mut self) -> Result<Selector, String> {
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('\'' | '"') => Ok(Selector::Name(self.string()?)),
+            _ => {
+                let start = self.integer()?;
+                self.skip_spaces();
+                if self.peek() != Some(':') {
+                    return start.map(Selector::Index).ok_or_else(|| format!("expected an index at position {}", self.pos));
+                }
+                self.pos += 1;
+                self.skip_spaces();
+                let end = self.integer()?;
+                self.skip_spaces();
+                let step = if self.eat(":") {
+                    self.skip_spaces();
+                    self.integer()?.unwrap_or(1)
+                } else {
+                    1
+                };
+                Ok(Selector::Slice(start, end, step))
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>, String> {
+        let start = self.pos;
+        self.eat("-");
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Ok(None);
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        text.parse().map(Some).map_err(|_| format!("invalid number '{}'", text))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let quote = self.peek().ok_or("expected a string")?;
+        self.pos += 1;
+        let mut text = String::new();
+        loop {
+            match self.peek() {
+                None => return Err("unterminated string".to_string()),
+                Some(c) if c == quote => {
+                    self.pos += 1;
+                    return Ok(text);
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    let escaped = self.peek().ok_or("unterminated string")?;
+                    text.push(escaped);
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.and()?;
+        loop {
+            self.skip_spaces();
+            if !self.eat("||") {
+                self.depth = depth;
+                return Ok(expr);
+            }
+            self.deepen()?;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let depth = self.depth;
+        let mut expr = self.unary()?;
+        loop {
+            self.skip_spaces();
+            if !self.eat("&&") {
+                self.depth = depth;
+                return Ok(expr);
+            }
+            self.deepen()?;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        self.skip_spaces();
+        if self.peek() == Some('!') && self.peek_at(1) != Some('=') {
+            self.pos += 1;
+            self.deepen()?;
+            let expr = self.unary()?;
+            self.depth -= 1;
+            return Ok(Expr::Not(Box::new(expr)));
+        }
+        let left = self.operand()?;
+        self.skip_spaces();
+        if self.eat("=~") {
+            self.skip_spaces();
+            let pattern = match self.peek() {
+                Some('\'' | '"') => self.string()?,
+                Some('/') => {
+                    // `/pattern/` as in most JSONPath implementations.
+                    let start = self.pos + 1;
+                    self.pos = start;
+                    while self.peek().is_some_and(|c| c != '/') {
+                        self.pos += 1;
+                    }
+                    let pattern = self.chars[start..self.pos].iter().collect();
+                    self.expect("/")?;
+                    pattern
+                }
+                _ => return Err(format!("expected a pattern at position {}", self.pos)),
+            };
+            let regex = Regex::new(&pattern).map_err(|e| format!("invalid pattern '{}': {}", pattern, e))?;
+            return Ok(Expr::Matches(Box::new(left), regex));
+        }
+        let op = [("==", CompareOp::Eq), ("!=", CompareOp::Ne), ("<=", CompareOp::Le), (">=", CompareOp::Ge), ("<", CompareOp::Lt), (">", CompareOp::Gt)]
+            .into_iter()
+            .find(|(symbol, _)| self.eat(symbol));
+        match op {
+            Some((_, op)) => Ok(Expr::Compare(Box::new(left), op, Box::new(self.operand()?))),
+            None => Ok(left),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Expr, String> {
+        self.skip_spaces();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                self.deepen()?;
+                let expr = self.or()?;
+                self.skip_spaces();
+                self.expect(")")?;
+                self.depth -= 1;
+                Ok(expr)
+            }
+            Some('@') => {
+                self.pos += 1;
+                Ok(Expr::Path { root: false, path: self.path(false)? })
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(Expr::Path { root: true, path: self.path(false)? })
+            }
+            Some('\'' | '"') => Ok(Expr::Literal(Value::String(self.string()?))),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_alphanumeric() || "-+.".contains(c)) {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                if word.is_empty() {
+                    return Err(format!("expected a value at position {}", start));
+                }
+                serde_json::from_str::<Value>(&word)
+                    .ok()
+                    .filter(|v| !v.is_object() && !v.is_array())
+                    .map(Expr::Literal)
+                    .ok_or_else(|| format!("unexpected '{}' at position {}", word, start))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doc() -> Value {
+        json!({
+            "data": {
+                "users": [
+                    { "name": "Ada", "age": 36, "active": true, "email": "ada@example.com" },
+                    { "name": "Bob", "age": 17, "active": false, "email": "bob@test.org" },
+                    { "name": "Cy", "age": 52, "email": "cy@example.com" }
+                ],
+                "content-type": "json"
+            },
+            "items": [0, 1, 2, 3, 4, 5],
+            "limit": 40
+        })
+    }
+
+    fn q(path: &str) -> Value {
+        query(&doc(), path).unwrap()
+    }
+
+    #[test]
+    fn definite_paths_return_one_value() {
+        assert_eq!(q("$.data.users[0].name"), json!("Ada"));
+        assert_eq!(q("data.users.1.name"), json!("Bob"));
+        assert_eq!(q("$['data']['content-type']"), json!("json"));
+        assert_eq!(q("$.items[-1]"), json!(5));
+        assert_eq!(q("$.items[-7]"), json!(null));
+        assert_eq!(q("$.missing.key"), json!(null));
+        assert_eq!(q("$"), doc());
+        assert!(JsonPath::parse("$.data.users[0]").unwrap().is_definite());
+        assert!(!JsonPath::parse("$.data.users[*]").unwrap().is_definite());
+    }
+
+    #[test]
+    fn lenient_paths_fall_back_to_dotted_keys() {
+        let value = json!({ "data": { "user name": "Ada", "content-type(x)": "json", "items": ["a", "b"] } });
+        let lenient = |path| JsonPath::parse_lenient(path).unwrap().query(&value);
+        assert_eq!(lenient("data.user name"), json!("Ada"));
+        assert_eq!(lenient("data.content-type(x)"), json!("json"));
+        assert_eq!(lenient("data.items.1"), json!("b"));
+        assert_eq!(lenient("data.items[0]"), json!("a"));
+        assert!(JsonPath::parse("data.user name").is_err());
+        assert!(JsonPath::parse_lenient("$.data.user name").is_err());
+    }
+
+    #[test]
+    fn wildcards_descendants_and_unions() {
+        assert_eq!(q("$.data.users[*].name"), json!(["Ada", "Bob", "Cy"]));
+        assert_eq!(q("$..age"), json!([36, 17, 52]));
+        assert_eq!(q("$.items[0,2]"), json!([0, 2]));
+        assert_eq!(q("$.data.users[0]['name','age']"), json!(["Ada", 36]));
+        assert_eq!(q("$.nothing[*]"), json!([]));
+    }
+
+    #[test]
+    fn slices() {
+        assert_eq!(q("$.items[1:3]"), json!([1, 2]));
+        assert_eq!(q("$.items[::2]"), json!([0, 2, 4]));
+        assert_eq!(q("$.items[-2:]"), json!([4, 5]));
+        assert_eq!(q("$.items[:100]"), json!([0, 1, 2, 3, 4, 5]));
+        assert_eq!(q("$.items[::-2]"), json!([5, 3, 1]));
+        assert_eq!(q("$.items[4:1:-1]"), json!([4, 3, 2]));
+        assert_eq!(q("$.items[::0]"), json!([]));
+        assert_eq!(q("$.items[3:1]"), json!([]));
+    }
+
+    #[test]
+    fn extreme_slice_steps_do_not_overflow() {
+        assert_eq!(q("$.items[1::9223372036854775807]"), json!([1]));
+        assert_eq!(q("$.items[::-9223372036854775808]"), json!([5]));
+        assert_eq!(q("$.items[-9223372036854775808:9223372036854775807]"), json!([0, 1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(q("$.data.users[?(@.age >= 18 && @.active)].name"), json!(["Ada"]));
+        assert_eq!(q("$.data.users[?(!@.active)].name"), json!(["Bob", "Cy"]));
+        assert_eq!(q("$.data.users[?(@.email =~ '.*@example\\.com')].name"), json!(["Ada", "Cy"]));
+        assert_eq!(q("$.data.users[?(@.email =~ /test/)].name"), json!(["Bob"]));
+        assert_eq!(q("$.data.users[?(@.age > $.limit)].name"), json!(["Cy"]));
+        assert_eq!(q("$.data.users[?(@.name == 'Bob' || (@.age < 40 && @.age > 30))].name"), json!(["Ada", "Bob"]));
+        assert_eq!(q("$.data.users[?(@.age != 17)].age"), json!([36, 52]));
+        // Comparing values of different types never holds.
+        assert_eq!(q("$.data.users[?(@.name < 5)]"), json!([]));
+    }
+
+    #[test]
+    fn find_is_none_when_nothing_matches() {
+        let path = |p: &str| JsonPath::parse(p).unwrap();
+        assert_eq!(path("$.data.users[0].age").find(&doc()), Some(json!(36)));
+        assert_eq!(path("$.data.nope").find(&doc()), None);
+        assert_eq!(path("$.data.users[?(@.age > 100)]").find(&doc()), None);
+        assert_eq!(path("$..name").find(&doc()), Some(json!(["Ada", "Bob", "Cy"])));
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let error = |p: &str| JsonPath::parse(p).unwrap_err();
+        assert_eq!(error("$.a]"), "unexpected ']' at position 3");
+        assert_eq!(error("$.a["), "expected an index at position 4");
+        assert_eq!(error("$['a"), "unterminated string");
+        assert_eq!(error("$.a[?(@.b == )]"), "expected a value at position 13");
+        assert_eq!(error("$.a[?(@.b =~ 5)]"), "expected a pattern at position 13");
+        assert!(error("$.a[?(@.b =~ '(')]").starts_with("invalid pattern '('"));
+        assert_eq!(error("$."), "expected a key at position 2");
+    }
+
+    #[test]
+    fn limits_filter_nesting() {
+        let nested = |open: &str, close: &str, n: usize| format!("$[?({}@.a{})]", open.repeat(n), close.repeat(n));
+        assert!(JsonPath::parse(&nested("(", ")", 10)).is_ok());
+        assert_eq!(JsonPath::parse(&nested("(", ")", 100_000)).unwrap_err(), "the path nests deeper than 64 levels");
+        assert!(JsonPath::parse(&nested("!", "", 100_000)).is_err());
+        assert!(JsonPath::parse(&format!("$[?(@.a{})]", " && @.a".repeat(100_000))).is_err());
+        assert!(JsonPath::parse(&format!("{}]", "$[?(@".repeat(100_000))).is_err());
+    }
+}
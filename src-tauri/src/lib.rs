@@ -1,7 +1,37 @@
+pub mod checkpoint;
+pub mod debugger;
+pub mod expression;
+pub mod jsonpath;
+pub mod migration;
+pub mod nodes;
+pub mod parameters;
+pub mod retry;
+pub mod subgraph;
+pub mod template;
+pub mod validation;
 pub mod workflow;
-use workflow::{Flow, WorkflowEngine};
+use checkpoint::Checkpoint;
+use debugger::{Debugger, PausedState};
+use workflow::{ExecutionEvent, Flow, FlowRun, WorkflowEngine};
 use std::fs;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::{Emitter, Manager};
+use tokio_util::sync::CancellationToken;
+
+/// Cancellation tokens of the flows currently running, keyed by run id.
+#[derive(Default)]
+struct RunningFlows(Mutex<HashMap<String, CancellationToken>>);
+
+/// Debuggers attached to flows started in debug mode, keyed by run id.
+#[derive(Default)]
+struct DebugSessions(Mutex<HashMap<String, Debugger>>);
+
+/// The outcome of the latest finished run of each flow, keyed by the flow key
+/// its canvas sends along, used to seed "run from here".
+#[derive(Default)]
+struct LastRuns(Mutex<HashMap<String, FlowRun>>);
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -9,28 +39,176 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
+/// Where the GUI keeps the checkpoint of each run, in the app's data directory.
+fn checkpoint_path(app: &tauri::AppHandle, run_id: &str) -> Option<PathBuf> {
+    let dir = app.path().app_data_dir().ok()?;
+    Some(dir.join("checkpoints").join(format!("{}.json", run_id)))
+}
+
+/// Builds the engine for a GUI run and registers it under `run_id` for the
+/// cancel and debug commands.
+fn start_run(app: &tauri::AppHandle, run_id: &Option<String>, debug: bool) -> (WorkflowEngine, CancellationToken) {
+    let cancel = CancellationToken::new();
+
+    // Forward progress to the canvas as "flow-event" events while the flow runs
+    let emitter = app.clone();
+    let mut engine = WorkflowEngine::new().with_observer(move |event: &ExecutionEvent| {
+        let _ = emitter.emit("flow-event", event);
+    });
+
+    if let Some(id) = run_id {
+        app.state::<RunningFlows>().0.lock().unwrap().insert(id.clone(), cancel.clone());
+        if let Some(path) = checkpoint_path(app, id) {
+            engine = engine.with_checkpoints(path);
+        }
+        // Debug mode pauses at breakpoints; the debug_* commands drive it by run id
+        if debug {
+            let debugger = Debugger::new();
+            app.state::<DebugSessions>().0.lock().unwrap().insert(id.clone(), debugger.clone());
+            engine = engine.with_debugger(debugger);
+        }
+    }
+    (engine, cancel)
+}
+
+fn finish_run(app: &tauri::AppHandle, run_id: &Option<String>, flow_key: &Option<String>, cancel: &CancellationToken, outcome: &Result<FlowRun, String>) {
+    if let (Ok(run), Some(key)) = (outcome, flow_key) {
+        app.state::<LastRuns>().0.lock().unwrap().insert(key.clone(), run.clone());
+    }
+    let Some(id) = run_id else { return };
+    app.state::<RunningFlows>().0.lock().unwrap().remove(id);
+    app.state::<DebugSessions>().0.lock().unwrap().remove(id);
+    // Only runs that failed or were stopped are worth resuming; skipped nodes are a normal finish
+    let resumable = cancel.is_cancelled() || outcome.as_ref().is_ok_and(|run| run.results.values().any(|r| r.is_error()));
+    if let (false, Some(path)) = (resumable, checkpoint_path(app, id)) {
+        Checkpoint::remove(path);
+    }
+}
+
+#[tauri::command]
+async fn execute_flow(app: tauri::AppHandle, flow: Flow, env: HashMap<String, String>, run_id: Option<String>, flow_key: Option<String>, debug: Option<bool>) -> Result<FlowRun, String> {
+    let (engine, cancel) = start_run(&app, &run_id, debug.unwrap_or(false));
+    let outcome = engine.execute(&flow, env_variables(env), &cancel).await.map_err(|e| e.to_string());
+    finish_run(&app, &run_id, &flow_key, &cancel, &outcome);
+    outcome
+}
+
+// Convert HashMap<String, String> to HashMap<String, Value> for internal engine
+fn env_variables(env: HashMap<String, String>) -> HashMap<String, serde_json::Value> {
+    env.into_iter().map(|(k, v)| (k, serde_json::Value::String(v))).collect()
+}
+
+/// Runs `node_id` and everything downstream of it. Upstream results and the
+/// variables come from `snapshot` (a saved run or checkpoint file) when given,
+/// otherwise from the last run of the flow with `flow_key`.
+#[tauri::command]
+async fn execute_from_node(app: tauri::AppHandle, flow: Flow, node_id: String, snapshot: Option<String>, run_id: Option<String>, flow_key: Option<String>, debug: Option<bool>) -> Result<FlowRun, String> {
+    let seed = match snapshot {
+        Some(path) => {
+            let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+            serde_json::from_str::<FlowRun>(&data).map_err(|e| e.to_string())?
+        }
+        None => flow_key.as_ref()
+            .and_then(|key| app.state::<LastRuns>().0.lock().unwrap().get(key).cloned())
+            .ok_or("Run the flow once before running from a node")?,
+    };
+    let (engine, cancel) = start_run(&app, &run_id, debug.unwrap_or(false));
+
+    let outcome = engine.execute_from(&flow, &node_id, &seed, &cancel).await.map_err(|e| e.to_string());
+    finish_run(&app, &run_id, &flow_key, &cancel, &outcome);
+    outcome
+}
+
+/// Runs `node_id` and only the nodes it depends on.
 #[tauri::command]
-async fn execute_flow(flow: Flow, env: HashMap<String, String>) -> Result<(HashMap<String, workflow::ExecutionResult>, HashMap<String, serde_json::Value>), String> {
-    let engine = WorkflowEngine::new();
-    // Convert HashMap<String, String> to HashMap<String, Value> for internal engine
-    let mut variables: HashMap<String, serde_json::Value> = HashMap::new();
-    for (k, v) in env {
-        variables.insert(k, serde_json::Value::String(v));
+async fn execute_until_node(app: tauri::AppHandle, flow: Flow, node_id: String, env: HashMap<String, String>, run_id: Option<String>, flow_key: Option<String>, debug: Option<bool>) -> Result<FlowRun, String> {
+    let (engine, cancel) = start_run(&app, &run_id, debug.unwrap_or(false));
+    let outcome = engine.execute_until(&flow, &node_id, env_variables(env), &cancel).await.map_err(|e| e.to_string());
+    finish_run(&app, &run_id, &flow_key, &cancel, &outcome);
+    outcome
+}
+
+/// Continues the failed or cancelled run `from_run_id` from its last checkpoint,
+/// re-running the nodes that failed instead of the whole flow. Secret
+/// parameters aren't saved in checkpoints, so they come from `env` again.
+#[tauri::command]
+async fn resume_flow(app: tauri::AppHandle, flow: Flow, from_run_id: String, env: HashMap<String, String>, run_id: Option<String>, flow_key: Option<String>, debug: Option<bool>) -> Result<FlowRun, String> {
+    let saved_path = checkpoint_path(&app, &from_run_id).ok_or("Checkpoints are unavailable: no app data directory")?;
+    let mut saved = Checkpoint::load(&saved_path).map_err(|e| e.to_string())?;
+    saved.variables.extend(env_variables(env).into_iter().filter(|(name, _)| flow.is_secret(name)));
+    flow.bind_secret_parameters(&mut saved.variables).map_err(|e| e.to_string())?;
+    let (engine, cancel) = start_run(&app, &run_id, debug.unwrap_or(false));
+
+    let outcome = engine.resume(&flow, saved, &cancel).await.map_err(|e| e.to_string());
+    finish_run(&app, &run_id, &flow_key, &cancel, &outcome);
+    // The new run keeps its own checkpoint from here on
+    if run_id.as_ref().is_some_and(|id| *id != from_run_id) {
+        Checkpoint::remove(saved_path);
     }
-    
-    engine.execute(&flow, variables).await.map_err(|e| e.to_string())
+    outcome
+}
+
+fn debug_session(sessions: &DebugSessions, run_id: &str) -> Result<Debugger, String> {
+    sessions.0.lock().unwrap().get(run_id).cloned().ok_or_else(|| format!("No debug session for run '{}'", run_id))
+}
+
+/// Variables and results of a paused run, or null while it is running.
+#[tauri::command]
+fn debug_state(sessions: tauri::State<'_, DebugSessions>, run_id: String) -> Result<Option<PausedState>, String> {
+    Ok(debug_session(&sessions, &run_id)?.paused())
+}
+
+#[tauri::command]
+fn debug_set_variable(sessions: tauri::State<'_, DebugSessions>, run_id: String, name: String, value: serde_json::Value) -> Result<(), String> {
+    debug_session(&sessions, &run_id)?.set_variable(name, value)
+}
+
+/// Runs the next node and pauses again. Returns false if the run was not paused.
+#[tauri::command]
+fn debug_step(sessions: tauri::State<'_, DebugSessions>, run_id: String) -> Result<bool, String> {
+    Ok(debug_session(&sessions, &run_id)?.step())
+}
+
+/// Runs until the next breakpoint. Returns false if the run was not paused.
+#[tauri::command]
+fn debug_continue(sessions: tauri::State<'_, DebugSessions>, run_id: String) -> Result<bool, String> {
+    Ok(debug_session(&sessions, &run_id)?.continue_run())
+}
+
+/// Stops the flow started with `run_id`. Returns false if no such run is active.
+#[tauri::command]
+fn cancel_flow(runs: tauri::State<'_, RunningFlows>, run_id: String) -> bool {
+    match runs.0.lock().unwrap().get(&run_id) {
+        Some(token) => {
+            token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+#[tauri::command]
+fn validate_flow(flow: Flow) -> Vec<validation::Diagnostic> {
+    flow.validate()
+}
+
+/// Evaluates a JSONPath against a value, exactly as `capture` and `arrayMap` would.
+#[tauri::command]
+fn query_json(value: serde_json::Value, path: String) -> Result<serde_json::Value, String> {
+    jsonpath::query(&value, &path)
 }
 
 #[tauri::command]
-fn save_flow(path: String, flow: Flow) -> Result<(), String> {
+fn save_flow(path: String, mut flow: Flow) -> Result<(), String> {
+    // Whatever the canvas was loaded from, it is saved in the current format
+    flow.version = migration::CURRENT_VERSION;
     let data = serde_json::to_string_pretty(&flow).map_err(|e| e.to_string())?;
     fs::write(path, data).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 fn load_flow(path: String) -> Result<Flow, String> {
-    let data = fs::read_to_string(path).map_err(|e| e.to_string())?;
-    serde_json::from_str(&data).map_err(|e| e.to_string())
+    Flow::load(path).map_err(|e| format!("{:#}", e))
 }
 
 use serde::{Deserialize, Serialize};
@@ -125,9 +303,22 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
+        .manage(RunningFlows::default())
+        .manage(DebugSessions::default())
+        .manage(LastRuns::default())
         .invoke_handler(tauri::generate_handler![
             greet, 
             execute_flow, 
+            resume_flow,
+            execute_from_node,
+            execute_until_node,
+            cancel_flow,
+            debug_state,
+            debug_set_variable,
+            debug_step,
+            debug_continue,
+            validate_flow,
+            query_json,
             save_flow, 
             load_flow, 
             save_request_template, 
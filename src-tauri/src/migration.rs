@@ -0,0 +1,100 @@
+//! Upgrades flow documents written by older versions of Knotwork.
+//!
+//! Every flow file carries a `version` (files from before versioning have
+//! none and count as version 0). Migrations work on the raw JSON, one version
+//! at a time, so a file of any age is brought up to [`CURRENT_VERSION`] before
+//! it is turned into a [`Flow`]. To change the format, bump `CURRENT_VERSION`
+//! and append the step that upgrades the previous version to [`MIGRATIONS`].
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+use crate::workflow::Flow;
+
+/// The flow format written by this build.
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Upgrades a version `from` document to version `from + 1`.
+struct Migration {
+    from: u32,
+    description: &'static str,
+    apply: fn(&mut Value),
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration { from: 0, description: "replace display and tabulize nodes with debug nodes, drop saved run state", apply: v0_to_v1 },
+];
+
+/// A document brought up to [`CURRENT_VERSION`].
+#[derive(Debug)]
+pub struct Migrated {
+    pub document: Value,
+    /// The version the document had before.
+    pub from_version: u32,
+    /// What each applied step did, oldest first; empty if it was already current.
+    pub applied: Vec<&'static str>,
+}
+
+/// The version a raw flow document declares.
+pub fn version_of(document: &Value) -> u32 {
+    document.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32
+}
+
+/// Runs every migration the document still needs. Documents newer than this
+/// build are refused rather than guessed at.
+pub fn migrate(mut document: Value) -> Result<Migrated> {
+    let from_version = version_of(&document);
+    if from_version > CURRENT_VERSION {
+        bail!("Flow version {} is newer than this build supports (up to {})", from_version, CURRENT_VERSION);
+    }
+    if !document.is_object() {
+        bail!("A flow document must be a JSON object");
+    }
+
+    let mut applied = Vec::new();
+    for step in MIGRATIONS.iter().filter(|m| m.from >= from_version) {
+        (step.apply)(&mut document);
+        document["version"] = Value::from(step.from + 1);
+        applied.push(step.description);
+    }
+    Ok(Migrated { document, from_version, applied })
+}
+
+impl Flow {
+    /// Parses a flow document of any version.
+    pub fn from_json(text: &str) -> Result<Flow> {
+        let document: Value = serde_json::from_str(text)?;
+        let migrated = migrate(document)?;
+        Ok(serde_json::from_value(migrated.document)?)
+    }
+
+    /// Reads a flow file of any version.
+    pub fn load(path: impl AsRef<Path>) -> Result<Flow> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read flow {:?}", path))?;
+        Flow::from_json(&text).with_context(|| format!("Failed to load flow {:?}", path))
+    }
+}
+
+/// Version 0 files come from builds that still had separate `display` and
+/// `tabulize` viewers, and that saved the canvas' run state into node data.
+fn v0_to_v1(document: &mut Value) {
+    let Some(nodes) = document.get_mut("nodes").and_then(|n| n.as_array_mut()) else { return };
+    for node in nodes {
+        let viewer = matches!(node.get("type").and_then(|t| t.as_str()), Some("display" | "tabulize" | "debug"));
+        if viewer {
+            node["type"] = Value::from("debug");
+        }
+        if let Some(data) = node.get_mut("data").and_then(|d| d.as_object_mut()) {
+            for key in ["executionResult", "executionHistory", "running"] {
+                data.remove(key);
+            }
+            // Viewers kept the last value they displayed; the next run fills it in again.
+            if viewer {
+                data.remove("input");
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+//! Declared inputs and outputs of a flow.
+//!
+//! A flow may list the variables it expects to be given (`parameters`) and the
+//! variables it promises to set (`outputs`). Parameters are checked and filled
+//! in from their defaults before a run starts, so a missing `api_key` fails up
+//! front instead of as an empty header halfway through the flow.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::workflow::Flow;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ParameterType {
+    #[default]
+    String,
+    Number,
+    Boolean,
+    /// Any JSON value; strings holding JSON are parsed.
+    Json,
+}
+
+/// A variable the flow expects to be given when it starts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowParameter {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub param_type: ParameterType,
+    /// Used when no value is given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<serde_json::Value>,
+    /// A run without a value (or default) for it is refused.
+    #[serde(default)]
+    pub required: bool,
+    /// Never shown when prompting for it or echoing it back.
+    #[serde(default)]
+    pub secret: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+/// A variable the flow sets for its caller; reported in [`FlowRun::outputs`](crate::workflow::FlowRun::outputs).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowOutput {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl ParameterType {
+    /// Converts `value` to this type. Strings are parsed, since values given on
+    /// the command line or in the environment always arrive as strings.
+    pub fn coerce(self, value: &serde_json::Value) -> Result<serde_json::Value, String> {
+        use serde_json::Value;
+        match (self, value) {
+            (ParameterType::String, Value::String(_)) => Ok(value.clone()),
+            (ParameterType::String, Value::Number(_) | Value::Bool(_)) => Ok(Value::String(value.to_string())),
+            (ParameterType::Number, Value::Number(_)) => Ok(value.clone()),
+            (ParameterType::Number, Value::String(s)) => serde_json::from_str::<serde_json::Number>(s.trim())
+                .map(Value::Number)
+                .map_err(|_| format!("'{}' is not a number", s)),
+            (ParameterType::Boolean, Value::Bool(_)) => Ok(value.clone()),
+            (ParameterType::Boolean, Value::String(s)) => match s.trim() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => Err(format!("'{}' is not true or false", s)),
+            },
+            (ParameterType::Json, Value::String(s)) => Ok(serde_json::from_str(s).unwrap_or_else(|_| value.clone())),
+            (ParameterType::Json, _) => Ok(value.clone()),
+            (_, _) => Err(format!("expected a {}, got {}", self.as_str(), value)),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ParameterType::String => "string",
+            ParameterType::Number => "number",
+            ParameterType::Boolean => "boolean",
+            ParameterType::Json => "json",
+        }
+    }
+}
+
+impl Flow {
+    /// Checks `variables` against the declared parameters: given values are
+    /// converted to the declared type, missing ones take their default, and
+    /// missing required ones are an error. Undeclared variables pass through.
+    pub fn bind_parameters(&self, variables: &mut HashMap<String, serde_json::Value>) -> Result<()> {
+        self.bind(variables, |_| true)
+    }
+
+    /// [`Flow::bind_parameters`] for the secret parameters only, which a
+    /// [`Checkpoint`](crate::checkpoint::Checkpoint) doesn't keep.
+    pub fn bind_secret_parameters(&self, variables: &mut HashMap<String, serde_json::Value>) -> Result<()> {
+        self.bind(variables, |param| param.secret)
+    }
+
+    /// Whether `name` is a parameter marked `secret`.
+    pub fn is_secret(&self, name: &str) -> bool {
+        self.parameters.iter().any(|p| p.secret && p.name == name)
+    }
+
+    fn bind(&self, variables: &mut HashMap<String, serde_json::Value>, include: impl Fn(&FlowParameter) -> bool) -> Result<()> {
+        let mut problems = Vec::new();
+        for param in self.parameters.iter().filter(|p| include(p)) {
+            match variables.get(&param.name).or(param.default.as_ref()) {
+                Some(value) => match param.param_type.coerce(value) {
+                    Ok(value) => {
+                        variables.insert(param.name.clone(), value);
+                    }
+                    Err(e) => problems.push(format!("parameter '{}': {}", param.name, e)),
+                },
+                None if param.required => problems.push(format!("missing required parameter '{}'", param.name)),
+                None => {}
+            }
+        }
+        if !problems.is_empty() {
+            bail!("Invalid flow parameters: {}", problems.join("; "));
+        }
+        Ok(())
+    }
+
+    /// Required parameters that `variables` doesn't provide and that have no default.
+    pub fn missing_parameters(&self, variables: &HashMap<String, serde_json::Value>) -> Vec<&FlowParameter> {
+        self.parameters.iter()
+            .filter(|p| p.required && p.default.is_none() && !variables.contains_key(&p.name))
+            .collect()
+    }
+
+    /// The declared outputs among `variables`.
+    pub fn collect_outputs(&self, variables: &HashMap<String, serde_json::Value>) -> HashMap<String, serde_json::Value> {
+        self.outputs.iter()
+            .filter_map(|o| variables.get(&o.name).map(|value| (o.name.clone(), value.clone())))
+            .collect()
+    }
+}
@@ -0,0 +1,165 @@
+//! Declarative retry policies configured per node under `data.retry`.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::workflow::{ExecutionResult, ExecutionStatus, Node};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Backoff {
+    Fixed,
+    #[default]
+    Exponential,
+}
+
+/// How often and how patiently a failing node is re-run.
+///
+/// A node is retried when it returns an `error` result, or when its output
+/// carries an HTTP `status` listed in `retry_on`. The node's timeout applies to
+/// each attempt on its own, so an attempt that hangs is cut off and retried.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Total number of runs, including the first one.
+    pub max_attempts: u32,
+    pub backoff: Backoff,
+    /// Delay before the first retry; exponential backoff doubles it each time.
+    pub delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Waits a random 50-100% of the computed delay so clients don't retry in lockstep.
+    pub jitter: bool,
+    pub retry_on: Vec<u16>,
+    /// Honour a `Retry-After` response header instead of the computed delay,
+    /// up to `max_delay_ms`.
+    pub respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff: Backoff::Exponential,
+            delay_ms: 500,
+            max_delay_ms: 30_000,
+            jitter: true,
+            retry_on: vec![429, 500, 502, 503, 504],
+            respect_retry_after: true,
+        }
+    }
+}
+
+/// One run of a node under a retry policy, kept in its result for debugging.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attempt {
+    pub attempt: u32,
+    pub status: ExecutionStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_status: Option<u16>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    /// How long the engine waited before the next attempt, if there was one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_in_ms: Option<u64>,
+}
+
+impl RetryPolicy {
+    /// The node's policy, if `data.retry` is set. A malformed policy is reported
+    /// by validation and otherwise ignored.
+    pub fn for_node(node: &Node) -> Option<Self> {
+        Self::parse(node.data.get("retry")?).ok()
+    }
+
+    pub fn parse(value: &serde_json::Value) -> Result<Self, String> {
+        let policy: Self = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+        if policy.max_attempts == 0 {
+            return Err("maxAttempts must be at least 1".to_string());
+        }
+        Ok(policy)
+    }
+
+    /// How long to wait before running again after `attempt` produced `result`,
+    /// or `None` when the node should not be retried.
+    pub fn next_delay(&self, attempt: u32, result: &ExecutionResult) -> Option<Duration> {
+        if attempt >= self.max_attempts {
+            return None;
+        }
+        let http_status = http_status(result);
+        let retryable = result.is_error() || http_status.is_some_and(|s| self.retry_on.contains(&s));
+        if !retryable {
+            return None;
+        }
+
+        if self.respect_retry_after {
+            if let Some(delay) = retry_after(result) {
+                return Some(delay.min(Duration::from_millis(self.max_delay_ms)));
+            }
+        }
+
+        let base = match self.backoff {
+            Backoff::Fixed => self.delay_ms,
+            Backoff::Exponential => self.delay_ms.saturating_mul(1u64 << (attempt - 1).min(32)),
+        };
+        let delay = base.min(self.max_delay_ms);
+        let delay = if self.jitter && delay > 0 {
+            rand::thread_rng().gen_range(delay / 2..=delay)
+        } else {
+            delay
+        };
+        Some(Duration::from_millis(delay))
+    }
+}
+
+/// The HTTP status a node reported in its output, if any.
+pub fn http_status(result: &ExecutionResult) -> Option<u16> {
+    result.output.get("status")?.as_u64().and_then(|s| u16::try_from(s).ok())
+}
+
+/// Parses a `Retry-After` header (delta-seconds or HTTP-date) from the output's `headers`.
+fn retry_after(result: &ExecutionResult) -> Option<Duration> {
+    let value = result.output.get("headers")?.get("retry-after")?.as_str()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value).ok()?;
+    Some(at.duration_since(std::time::SystemTime::now()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(value: serde_json::Value) -> RetryPolicy {
+        RetryPolicy::parse(&value).unwrap()
+    }
+
+    fn response(status: u16, retry_after: Option<&str>) -> ExecutionResult {
+        let headers = retry_after.map_or(serde_json::json!({}), |v| serde_json::json!({ "retry-after": v }));
+        ExecutionResult::success("n", serde_json::json!({ "status": status, "headers": headers }))
+    }
+
+    #[test]
+    fn backs_off_up_to_the_limit() {
+        let policy = policy(serde_json::json!({ "maxAttempts": 10, "delayMs": 100, "maxDelayMs": 500, "jitter": false }));
+        let delays: Vec<u64> = (1..=5).map(|n| policy.next_delay(n, &response(503, None)).unwrap().as_millis() as u64).collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+        assert_eq!(policy.next_delay(10, &response(503, None)), None);
+        assert_eq!(policy.next_delay(1, &response(200, None)), None);
+        assert_eq!(policy.next_delay(1, &ExecutionResult::error("n", "boom")), Some(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn retry_after_is_capped_by_max_delay() {
+        let policy = policy(serde_json::json!({ "delayMs": 100, "maxDelayMs": 5000, "jitter": false }));
+        assert_eq!(policy.next_delay(1, &response(429, Some("2"))), Some(Duration::from_secs(2)));
+        assert_eq!(policy.next_delay(1, &response(429, Some("86400"))), Some(Duration::from_millis(5000)));
+        assert_eq!(policy.next_delay(1, &response(429, Some("Fri, 31 Dec 9999 23:59:59 GMT"))), Some(Duration::from_millis(5000)));
+    }
+
+    #[test]
+    fn rejects_bad_policies() {
+        assert_eq!(RetryPolicy::parse(&serde_json::json!({ "maxAttempts": 0 })), Err("maxAttempts must be at least 1".to_string()));
+        assert!(RetryPolicy::parse(&serde_json::json!({ "attempts": 3 })).is_err());
+    }
+}
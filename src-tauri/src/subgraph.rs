@@ -0,0 +1,141 @@
+//! Running part of a flow: everything downstream of a node, or everything a node depends on.
+
+use anyhow::{bail, Result};
+use std::collections::{HashMap, HashSet};
+use tokio_util::sync::CancellationToken;
+
+use crate::checkpoint::{Checkpoint, QueuedNode};
+use crate::workflow::{Edge, Flow, FlowRun, JoinMode, WorkflowEngine};
+
+impl Flow {
+    /// Ids of the nodes `node_id` can reach, not counting itself unless it is on a cycle.
+    pub fn descendants(&self, node_id: &str) -> HashSet<String> {
+        self.reachable(node_id, |edge| (edge.source.as_str(), edge.target.as_str()))
+    }
+
+    /// Ids of the nodes that can reach `node_id`, not counting itself unless it is on a cycle.
+    pub fn ancestors(&self, node_id: &str) -> HashSet<String> {
+        self.reachable(node_id, |edge| (edge.target.as_str(), edge.source.as_str()))
+    }
+
+    fn reachable<'a>(&'a self, node_id: &str, direction: impl Fn(&'a Edge) -> (&'a str, &'a str)) -> HashSet<String> {
+        let mut next: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in &self.edges {
+            let (from, to) = direction(edge);
+            next.entry(from).or_default().push(to);
+        }
+        let mut seen = HashSet::new();
+        let mut stack: Vec<&str> = next.get(node_id).cloned().unwrap_or_default();
+        while let Some(id) = stack.pop() {
+            if seen.insert(id.to_string()) {
+                stack.extend(next.get(id).into_iter().flatten());
+            }
+        }
+        seen
+    }
+
+    /// The part of the flow `node_id` depends on: the node, its ancestors and the edges between them.
+    pub fn upstream_of(&self, node_id: &str) -> Result<Flow> {
+        if !self.nodes.iter().any(|n| n.id == node_id) {
+            bail!("Node '{}' is not part of the flow", node_id);
+        }
+        let mut keep = self.ancestors(node_id);
+        keep.insert(node_id.to_string());
+        Ok(Flow {
+            version: self.version,
+            nodes: self.nodes.iter().filter(|n| keep.contains(&n.id)).cloned().collect(),
+            edges: self.edges.iter().filter(|e| keep.contains(&e.source) && keep.contains(&e.target)).cloned().collect(),
+            settings: self.settings.clone(),
+            parameters: self.parameters.clone(),
+            outputs: self.outputs.clone(),
+        })
+    }
+}
+
+impl WorkflowEngine {
+    /// Runs `target` and only the nodes it depends on.
+    pub async fn execute_until(&self, flow: &Flow, target: &str, initial_variables: HashMap<String, serde_json::Value>, cancel: &CancellationToken) -> Result<FlowRun> {
+        self.execute(&flow.upstream_of(target)?, initial_variables, cancel).await
+    }
+
+    /// Runs `start` and everything downstream of it. The rest of the flow is not
+    /// run again: its results and the variables are taken from `seed`, usually
+    /// the previous run or a saved snapshot of it. A `seed` with results for
+    /// nodes this flow doesn't have comes from some other flow and is refused.
+    pub async fn execute_from(&self, flow: &Flow, start: &str, seed: &FlowRun, cancel: &CancellationToken) -> Result<FlowRun> {
+        let Some(start_node) = flow.nodes.iter().find(|n| n.id == start) else {
+            bail!("Node '{}' is not part of the flow", start);
+        };
+        let mut unknown: Vec<&str> = seed.results.keys()
+            .filter(|id| !flow.nodes.iter().any(|n| n.id == **id))
+            .map(|id| id.as_str())
+            .collect();
+        if !unknown.is_empty() {
+            unknown.sort();
+            bail!("The previous run doesn't match this flow (it has no node {}); run the whole flow again first", unknown.join(", "));
+        }
+        let mut rerun = flow.descendants(start);
+        rerun.insert(start.to_string());
+
+        // Results of nodes that are about to run again would only be stale.
+        let results: HashMap<_, _> = seed.results.iter()
+            .filter(|(id, _)| !rerun.contains(*id))
+            .map(|(id, result)| (id.clone(), result.clone()))
+            .collect();
+        let seeded_edges = |node_id: &str| -> Vec<String> {
+            flow.edges.iter()
+                .filter(|e| e.target == node_id && results.get(&e.source).is_some_and(|r| r.fires(e)))
+                .map(|e| e.id.clone())
+                .collect()
+        };
+
+        // Barriers downstream count the parents that won't run again as already arrived.
+        let pending_joins = flow.nodes.iter()
+            .filter(|n| n.id != start && rerun.contains(&n.id) && JoinMode::for_node(n) == JoinMode::All)
+            .map(|n| (n.id.clone(), seeded_edges(&n.id)))
+            .filter(|(_, arrived)| !arrived.is_empty())
+            .collect();
+
+        let current = vec![QueuedNode { node_id: start_node.id.clone(), arrived: seeded_edges(start) }];
+
+        let checkpoint = Checkpoint {
+            results,
+            variables: seed.variables.clone(),
+            current,
+            pending_joins,
+            ..Default::default()
+        };
+        self.resume(flow, checkpoint, cancel).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(ids: &[&str]) -> Flow {
+        let nodes: Vec<_> = ids.iter().map(|id| serde_json::json!({ "id": id, "type": "input", "data": { "value": id } })).collect();
+        let edges: Vec<_> = ids.windows(2).map(|w| serde_json::json!({ "id": format!("{}-{}", w[0], w[1]), "source": w[0], "target": w[1] })).collect();
+        Flow::from_json(&serde_json::json!({ "nodes": nodes, "edges": edges }).to_string()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn execute_from_reruns_downstream_only() {
+        let engine = WorkflowEngine::new();
+        let flow = flow(&["1", "2", "3"]);
+        let seed = engine.execute(&flow, HashMap::new(), &CancellationToken::new()).await.unwrap();
+
+        let run = engine.execute_from(&flow, "2", &seed, &CancellationToken::new()).await.unwrap();
+        assert_eq!(run.trace.iter().map(|r| r.node_id.as_str()).collect::<Vec<_>>(), ["2", "3"]);
+        assert_eq!(run.results.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn execute_from_refuses_a_run_of_another_flow() {
+        let engine = WorkflowEngine::new();
+        let other = engine.execute(&flow(&["1", "2", "9"]), HashMap::new(), &CancellationToken::new()).await.unwrap();
+
+        let error = engine.execute_from(&flow(&["1", "2", "3"]), "2", &other, &CancellationToken::new()).await.unwrap_err();
+        assert_eq!(error.to_string(), "The previous run doesn't match this flow (it has no node 9); run the whole flow again first");
+    }
+}
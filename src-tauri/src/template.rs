@@ -0,0 +1,518 @@
+//! The `{{ ... }}` template language used in node settings.
+//!
+//! A placeholder names a value and can pipe it through filters:
+//!
+//! - `{{token}}`, `{{user.id}}`, `{{items[0].name}}`, `{{items[-1]}}`, `{{headers["content-type"]}}`
+//! - `{{nodes.login.output.data.token}}` reads another node's latest result
+//!   (its `output`, `status`, `error`, ...); `nodes["id with spaces"]` works too
+//! - `{{name | default("anonymous") | upper}}`
+//!
+//! Filters are `default(value)`, `upper`, `lower`, `trim`, `urlencode`, `json`
+//! and `base64`. Strings are inserted as they are and anything else as JSON.
+//! A placeholder that can't be resolved is left in the text untouched, or is
+//! an error in strict mode.
+//!
+//! Names starting with `$` are built-in functions, evaluated afresh for every
+//! placeholder:
+//!
+//! - `{{$uuid}}`: a random UUID (v4)
+//! - `{{$now}}`, `{{$now("rfc3339")}}`, `{{$now("unix")}}`, `{{$now("unix_ms")}}`
+//!   or `{{$now("%Y-%m-%d")}}`: the current UTC time
+//! - `{{$timestamp}}`: seconds since the Unix epoch
+//! - `{{$randomInt(1, 100)}}` (both ends included) and `{{$random}}` (0 to 1)
+//! - `{{$env("HOME")}}`: an environment variable of the process running the flow
+//!
+//! Random values come from the run's generator, which `settings.seed` makes
+//! reproducible.
+
+use base64::Engine as _;
+use chrono::{SecondsFormat, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt::{self, Write as _};
+use std::sync::Mutex;
+
+use crate::workflow::ExecutionResult;
+
+/// Root name under which node results are reachable.
+pub const NODES_ROOT: &str = "nodes";
+
+/// Characters `urlencode` leaves alone (RFC 3986 unreserved).
+const URL_SAFE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'_').remove(b'.').remove(b'~');
+
+/// How templates are rendered during one run.
+pub struct TemplateOptions {
+    /// Unresolved placeholders are an error rather than left in the text.
+    pub strict: bool,
+    /// The seed `rng` started from, if the run is meant to be reproducible.
+    pub seed: Option<u64>,
+    /// Source of `$uuid`, `$random` and `$randomInt`.
+    pub rng: Mutex<StdRng>,
+}
+
+impl TemplateOptions {
+    pub fn new(strict: bool, seed: Option<u64>) -> Self {
+        let rng = seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64);
+        Self { strict, seed, rng: Mutex::new(rng) }
+    }
+}
+
+/// What placeholders can refer to.
+#[derive(Clone, Copy)]
+pub struct Scope<'a> {
+    pub variables: &'a HashMap<String, Value>,
+    /// Latest result of every node that ran, for `{{nodes.<id>...}}`.
+    pub results: Option<&'a HashMap<String, ExecutionResult>>,
+    /// The run's random generator; without one, functions use the thread's.
+    pub rng: Option<&'a Mutex<StdRng>>,
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(variables: &'a HashMap<String, Value>) -> Self {
+        Self { variables, results: None, rng: None }
+    }
+
+    pub fn with_results(mut self, results: &'a HashMap<String, ExecutionResult>) -> Self {
+        self.results = Some(results);
+        self
+    }
+
+    pub fn with_rng(mut self, rng: &'a Mutex<StdRng>) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+
+    fn random<T>(&self, draw: impl FnOnce(&mut dyn rand::RngCore) -> T) -> T {
+        match self.rng {
+            Some(rng) => draw(&mut *rng.lock().unwrap_or_else(|e| e.into_inner())),
+            None => draw(&mut rand::thread_rng()),
+        }
+    }
+}
+
+/// A placeholder that could not be rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError {
+    /// The placeholder as written, braces included.
+    pub placeholder: String,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.placeholder, self.message)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+/// Renders every placeholder in `text`. Outside strict mode this never fails.
+pub fn render(text: &str, scope: &Scope, strict: bool) -> Result<String, TemplateError> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let Some(len) = placeholder_len(&rest[start..]) else {
+            // An unclosed `{{` is plain text.
+            out.push_str(&rest[start..]);
+            return Ok(out);
+        };
+        let placeholder = &rest[start..start + len];
+        match evaluate(&placeholder[2..len - 2], scope) {
+            Ok(value) => out.push_str(&to_text(&value)),
+            Err(message) if strict => return Err(TemplateError { placeholder: placeholder.to_string(), message }),
+            Err(_) => out.push_str(placeholder),
+        }
+        rest = &rest[start + len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Renders the placeholders in every string of a JSON document. A string that
+/// is nothing but one placeholder (whitespace aside), like `"{{count}}"`, becomes the value itself
+/// (a number stays a number, an object an object); placeholders inside longer
+/// strings are rendered as text. Object keys are rendered as text too.
+pub fn render_json(value: &Value, scope: &Scope, strict: bool) -> Result<Value, TemplateError> {
+    Ok(match value {
+        Value::String(text) => match sole_placeholder(text) {
+            Some(expression) => match evaluate(expression, scope) {
+                Ok(value) => value,
+                Err(message) if strict => return Err(TemplateError { placeholder: text.trim().to_string(), message }),
+                Err(_) => value.clone(),
+            },
+            None => Value::String(render(text, scope, strict)?),
+        },
+        Value::Array(items) => Value::Array(items.iter().map(|item| render_json(item, scope, strict)).collect::<Result<_, _>>()?),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, item)| Ok((render(key, scope, strict)?, render_json(item, scope, strict)?)))
+                .collect::<Result<_, TemplateError>>()?,
+        ),
+        other => other.clone(),
+    })
+}
+
+/// If `text` is exactly one placeholder, its expression (without the braces).
+pub fn sole_placeholder(text: &str) -> Option<&str> {
+    let trimmed = text.trim();
+    (trimmed.starts_with("{{") && placeholder_len(trimmed) == Some(trimmed.len())).then(|| &trimmed[2..trimmed.len() - 2])
+}
+
+/// Evaluates one placeholder expression, e.g. `user.name | upper`.
+pub fn evaluate(expression: &str, scope: &Scope) -> Result<Value, String> {
+    let mut parts = split_outside_quotes(expression, '|').into_iter();
+    let path = parts.next().unwrap_or_default().trim();
+    let mut value = resolve(path, scope)?;
+    for filter in parts {
+        value = apply_filter(filter.trim(), value).map_err(|e| format!("{} ({})", e, filter.trim()))?;
+    }
+    value.ok_or_else(|| format!("'{}' is not defined", path))
+}
+
+/// Strings as they are, everything else as JSON.
+pub fn to_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Length of the placeholder at the start of `text` up to its closing `}}`,
+/// ignoring braces inside quoted filter arguments.
+fn placeholder_len(text: &str) -> Option<usize> {
+    let mut quote = None;
+    let bytes = text.as_bytes();
+    let mut i = 2;
+    while i < bytes.len() {
+        match (quote, bytes[i]) {
+            (Some(q), b) if b == q => quote = None,
+            (Some(_), b'\\') => i += 1,
+            (Some(_), _) => {}
+            (None, b'"' | b'\'') => quote = Some(bytes[i]),
+            (None, b'}') if bytes.get(i + 1) == Some(&b'}') => return Some(i + 2),
+            (None, _) => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn split_outside_quotes(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match quote {
+            _ if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == separator => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            None => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+enum Segment {
+    Key(String),
+    Index(i64),
+}
+
+fn parse_path(path: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut chars = path.char_indices().peekable();
+    let mut expect_key = true;
+    while let Some(&(i, c)) = chars.peek() {
+        match c {
+            '[' => {
+                let close = path[i..].find(']').ok_or("unclosed '['")? + i;
+                let inner = path[i + 1..close].trim();
+                segments.push(match parse_literal(inner) {
+                    Some(Value::String(key)) => Segment::Key(key),
+                    Some(Value::Number(n)) if n.is_i64() => Segment::Index(n.as_i64().unwrap_or_default()),
+                    _ => return Err(format!("invalid index [{}]", inner)),
+                });
+                while chars.peek().is_some_and(|&(j, _)| j <= close) {
+                    chars.next();
+                }
+                expect_key = false;
+            }
+            '.' => {
+                chars.next();
+                expect_key = true;
+            }
+            _ if expect_key => {
+                let end = path[i..].find(['.', '[']).map_or(path.len(), |e| e + i);
+                let key = path[i..end].trim();
+                if key.is_empty() {
+                    return Err(format!("invalid path '{}'", path));
+                }
+                segments.push(Segment::Key(key.to_string()));
+                while chars.peek().is_some_and(|&(j, _)| j < end) {
+                    chars.next();
+                }
+                expect_key = false;
+            }
+            _ => return Err(format!("invalid path '{}'", path)),
+        }
+    }
+    if expect_key {
+        return Err(format!("invalid path '{}'", path));
+    }
+    Ok(segments)
+}
+
+/// Looks `path` up in the scope. `Ok(None)` means it parsed but names nothing.
+fn resolve(path: &str, scope: &Scope) -> Result<Option<Value>, String> {
+    // A variable whose name itself contains dots or brackets wins over path access.
+    if let Some(value) = scope.variables.get(path) {
+        return Ok(Some(value.clone()));
+    }
+    if let Some(call) = path.strip_prefix('$') {
+        return call_function(call, scope);
+    }
+    let segments = parse_path(path)?;
+    let (root, rest) = match segments.split_first() {
+        Some((Segment::Key(root), rest)) => (root.as_str(), rest),
+        _ => return Err(format!("invalid path '{}'", path)),
+    };
+
+    if root == NODES_ROOT {
+        if let Some(results) = scope.results {
+            let Some((Segment::Key(node_id), rest)) = rest.split_first() else {
+                return Err("expected a node id after 'nodes'".to_string());
+            };
+            let Some(result) = results.get(node_id) else { return Ok(None) };
+            let result = serde_json::to_value(result).map_err(|e| e.to_string())?;
+            return Ok(walk(&result, rest).cloned());
+        }
+    }
+    Ok(scope.variables.get(root).and_then(|value| walk(value, rest)).cloned())
+}
+
+fn walk<'v>(mut value: &'v Value, segments: &[Segment]) -> Option<&'v Value> {
+    for segment in segments {
+        value = match (segment, value) {
+            (Segment::Key(key), Value::Object(map)) => map.get(key)?,
+            (Segment::Key(key), Value::Array(items)) => items.get(key.parse::<usize>().ok()?)?,
+            (Segment::Index(index), Value::Array(items)) => {
+                // Negative indexes count from the end.
+                let index = if *index < 0 { items.len().checked_sub(index.unsigned_abs() as usize)? } else { *index as usize };
+                items.get(index)?
+            }
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Evaluates a built-in function call such as `randomInt(1, 6)` (the `$` already stripped).
+fn call_function(call: &str, scope: &Scope) -> Result<Option<Value>, String> {
+    let (name, args) = match call.find('(') {
+        Some(open) if call.ends_with(')') => {
+            let raw = call[open + 1..call.len() - 1].trim();
+            let args = if raw.is_empty() { Vec::new() } else { split_outside_quotes(raw, ',') };
+            let args = args.into_iter()
+                .map(|arg| parse_literal(arg).ok_or_else(|| format!("invalid argument {}", arg.trim())))
+                .collect::<Result<Vec<_>, _>>()?;
+            (call[..open].trim(), args)
+        }
+        _ => (call.trim(), Vec::new()),
+    };
+    let string_arg = |i: usize| args.get(i).map(|arg| arg.as_str().ok_or_else(|| format!("${} expects a string argument", name))).transpose();
+    let int_arg = |i: usize| args.get(i).and_then(|arg| arg.as_i64()).ok_or_else(|| format!("${} expects integer arguments", name));
+
+    let value = match name {
+        "uuid" => {
+            let bytes: [u8; 16] = scope.random(|rng| rng.gen());
+            Value::String(uuid::Builder::from_random_bytes(bytes).into_uuid().to_string())
+        }
+        "now" => {
+            let now = Utc::now();
+            match string_arg(0)?.unwrap_or("rfc3339") {
+                "rfc3339" | "iso" => Value::String(now.to_rfc3339_opts(SecondsFormat::Millis, true)),
+                "unix" => Value::from(now.timestamp()),
+                "unix_ms" => Value::from(now.timestamp_millis()),
+                format => {
+                    let mut text = String::new();
+                    write!(text, "{}", now.format(format)).map_err(|_| format!("invalid time format '{}'", format))?;
+                    Value::String(text)
+                }
+            }
+        }
+        "timestamp" => Value::from(Utc::now().timestamp()),
+        "random" => Value::from(scope.random(|rng| rng.gen::<f64>())),
+        "randomInt" => {
+            let (min, max) = (int_arg(0)?, int_arg(1)?);
+            if min > max {
+                return Err(format!("$randomInt: {} is greater than {}", min, max));
+            }
+            Value::from(scope.random(|rng| rng.gen_range(min..=max)))
+        }
+        "env" => {
+            let var = string_arg(0)?.ok_or("$env needs a variable name")?;
+            return Ok(std::env::var(var).ok().map(Value::String));
+        }
+        _ => return Err(format!("unknown function '${}'", name)),
+    };
+    Ok(Some(value))
+}
+
+/// A filter argument: a JSON literal, or a string in single quotes.
+fn parse_literal(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        return Some(Value::String(text[1..text.len() - 1].to_string()));
+    }
+    serde_json::from_str(text).ok()
+}
+
+fn apply_filter(filter: &str, value: Option<Value>) -> Result<Option<Value>, String> {
+    let (name, argument) = match filter.find('(') {
+        Some(open) if filter.ends_with(')') => {
+            let raw = &filter[open + 1..filter.len() - 1];
+            let argument = parse_literal(raw).ok_or_else(|| format!("invalid argument {}", raw))?;
+            (filter[..open].trim(), Some(argument))
+        }
+        _ => (filter, None),
+    };
+
+    if name == "default" {
+        let fallback = argument.ok_or("default needs a value")?;
+        return Ok(Some(value.filter(|v| !v.is_null()).unwrap_or(fallback)));
+    }
+    // Every other filter needs something to work on.
+    let Some(value) = value else { return Ok(None) };
+    let text = || to_text(&value);
+    let filtered = match name {
+        "upper" => Value::String(text().to_uppercase()),
+        "lower" => Value::String(text().to_lowercase()),
+        "trim" => Value::String(text().trim().to_string()),
+        "urlencode" => Value::String(utf8_percent_encode(&text(), URL_SAFE).to_string()),
+        "json" => Value::String(value.to_string()),
+        "base64" => Value::String(base64::engine::general_purpose::STANDARD.encode(text())),
+        _ => return Err(format!("unknown filter '{}'", name)),
+    };
+    Ok(Some(filtered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn vars(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn render_with(text: &str, variables: &HashMap<String, Value>) -> String {
+        render(text, &Scope::new(variables), false).unwrap()
+    }
+
+    #[test]
+    fn paths_reach_into_objects_and_arrays() {
+        let variables = vars(json!({
+            "user": { "id": 7, "name": "Ada" },
+            "items": [{ "name": "first" }, { "name": "last" }],
+            "headers": { "content-type": "text/plain" },
+            "a.b": "dotted",
+        }));
+        assert_eq!(render_with("{{user.id}} {{ user.name }}", &variables), "7 Ada");
+        assert_eq!(render_with("{{items[0].name}} {{items[-1].name}} {{items.1.name}}", &variables), "first last last");
+        assert_eq!(render_with(r#"{{headers["content-type"]}}"#, &variables), "text/plain");
+        assert_eq!(render_with("{{a.b}}", &variables), "dotted");
+        assert_eq!(render_with("{{user}}", &variables), r#"{"id":7,"name":"Ada"}"#);
+    }
+
+    #[test]
+    fn node_results_are_reachable_under_nodes() {
+        let variables = HashMap::new();
+        let results = HashMap::from([("login".to_string(), ExecutionResult::success("login".to_string(), json!({ "data": { "token": "abc" } })))]);
+        let scope = Scope::new(&variables).with_results(&results);
+        assert_eq!(render("{{nodes.login.output.data.token}}", &scope, true).unwrap(), "abc");
+        assert_eq!(render(r#"{{nodes["login"].status}}"#, &scope, true).unwrap(), "success");
+        assert_eq!(render("{{nodes.other.output}}", &scope, false).unwrap(), "{{nodes.other.output}}");
+    }
+
+    #[test]
+    fn filters_apply_left_to_right() {
+        let variables = vars(json!({ "name": "  Ada Lovelace ", "empty": null }));
+        assert_eq!(render_with("{{name | trim | upper}}", &variables), "ADA LOVELACE");
+        assert_eq!(render_with("{{name | trim | urlencode}}", &variables), "Ada%20Lovelace");
+        assert_eq!(render_with("{{missing | default('anonymous') | upper}}", &variables), "ANONYMOUS");
+        assert_eq!(render_with("{{empty | default(3)}}", &variables), "3");
+        assert_eq!(render_with("{{missing | default('a | b}}')}}", &variables), "a | b}}");
+        assert_eq!(render_with("{{name | trim | base64}}", &variables), "QWRhIExvdmVsYWNl");
+    }
+
+    #[test]
+    fn unresolved_placeholders_stay_unless_strict() {
+        let variables = vars(json!({ "x": 1 }));
+        let scope = Scope::new(&variables);
+        assert_eq!(render("a {{missing}} b {{x}} {{", &scope, false).unwrap(), "a {{missing}} b 1 {{");
+
+        let error = render("a {{missing}}", &scope, true).unwrap_err();
+        assert_eq!(error.to_string(), "{{missing}}: 'missing' is not defined");
+        let error = render("{{x | shout}}", &scope, true).unwrap_err();
+        assert_eq!(error.message, "unknown filter 'shout' (shout)");
+        let error = render("{{items[one]}}", &scope, true).unwrap_err();
+        assert_eq!(error.message, "invalid index [one]");
+        let error = render("{{$nope}}", &scope, true).unwrap_err();
+        assert_eq!(error.message, "unknown function '$nope'");
+    }
+
+    #[test]
+    fn a_sole_placeholder_keeps_its_type_in_json() {
+        let variables = vars(json!({ "count": 3, "user": { "id": 7 } }));
+        let scope = Scope::new(&variables);
+        let body = json!({ "count": "{{count}}", "label": "n={{count}}", "{{user.id}}": ["{{user}}"], "missing": " {{nope}} " });
+        assert_eq!(
+            render_json(&body, &scope, false).unwrap(),
+            json!({ "count": 3, "label": "n=3", "7": [{ "id": 7 }], "missing": " {{nope}} " }),
+        );
+        assert_eq!(render_json(&body, &scope, true).unwrap_err().placeholder, "{{nope}}");
+    }
+
+    #[test]
+    fn seeded_functions_repeat_across_runs() {
+        let variables = HashMap::new();
+        let text = "{{$uuid}} {{$random}} {{$randomInt(1, 6)}} {{$randomInt(-3, -3)}}";
+        let draw = |seed| {
+            let options = TemplateOptions::new(false, Some(seed));
+            render(text, &Scope::new(&variables).with_rng(&options.rng), true).unwrap()
+        };
+        assert_eq!(draw(42), draw(42));
+        assert_ne!(draw(42), draw(43));
+
+        let rendered = draw(42);
+        let parts: Vec<&str> = rendered.split(' ').collect();
+        assert_eq!(uuid::Uuid::parse_str(parts[0]).unwrap().get_version_num(), 4);
+        assert!((0.0..1.0).contains(&parts[1].parse::<f64>().unwrap()));
+        assert!((1..=6).contains(&parts[2].parse::<i64>().unwrap()));
+        assert_eq!(parts[3], "-3");
+    }
+
+    #[test]
+    fn function_arguments_are_checked() {
+        let variables = HashMap::new();
+        let scope = Scope::new(&variables);
+        let message = |text| render(text, &scope, true).unwrap_err().message;
+        assert_eq!(message("{{$randomInt(6, 1)}}"), "$randomInt: 6 is greater than 1");
+        assert_eq!(message("{{$randomInt('a', 1)}}"), "$randomInt expects integer arguments");
+        assert_eq!(message("{{$now(1)}}"), "$now expects a string argument");
+        assert_eq!(message("{{$env}}"), "$env needs a variable name");
+        assert_eq!(message("{{$randomInt(1, x)}}"), "invalid argument x");
+        assert!(render("{{$now(\"unix\")}}", &scope, true).unwrap().parse::<i64>().is_ok());
+    }
+}
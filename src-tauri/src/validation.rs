@@ -0,0 +1,363 @@
+//! Static checks run on a [`Flow`] before it is executed.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::retry::RetryPolicy;
+use crate::workflow::{Flow, WorkflowEngine};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    DuplicateNodeId,
+    DanglingEdge,
+    UnknownNodeType,
+    InvalidConfig,
+    UnreachableNode,
+    CycleWithoutLoop,
+    MissingServerTrigger,
+}
+
+/// One problem found in a flow. `node_id`, `edge_id` and `field` point at the
+/// offending part of the document when there is one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    pub message: String,
+    pub node_id: Option<String>,
+    pub edge_id: Option<String>,
+    pub field: Option<String>,
+}
+
+impl Diagnostic {
+    fn error(kind: DiagnosticKind, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, kind, message: message.into(), node_id: None, edge_id: None, field: None }
+    }
+
+    fn warning(kind: DiagnosticKind, message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, ..Self::error(kind, message) }
+    }
+
+    fn node(mut self, node_id: &str) -> Self {
+        self.node_id = Some(node_id.to_string());
+        self
+    }
+
+    fn edge(mut self, edge_id: &str) -> Self {
+        self.edge_id = Some(edge_id.to_string());
+        self
+    }
+
+    fn field(mut self, field: impl Into<String>) -> Self {
+        self.field = Some(field.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Whether the flow can't be run as it is. Other errors only concern the
+    /// nodes involved, which fail (or are cut short) when they run.
+    pub fn blocks_run(&self) -> bool {
+        matches!(self.kind, DiagnosticKind::DuplicateNodeId | DiagnosticKind::DanglingEdge | DiagnosticKind::MissingServerTrigger)
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(node_id) = &self.node_id {
+            write!(f, " (node {}", node_id)?;
+            if let Some(field) = &self.field {
+                write!(f, ", field {}", field)?;
+            }
+            write!(f, ")")?;
+        } else if let Some(edge_id) = &self.edge_id {
+            write!(f, " (edge {})", edge_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl Flow {
+    /// Checks the flow against the built-in node types.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        WorkflowEngine::new().validate(self)
+    }
+
+    /// Like [`Flow::validate`], additionally requiring what `knotty serve` needs.
+    pub fn validate_for_serve(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = self.validate();
+        if !self.nodes.iter().any(|n| n.node_type == "serverTrigger") {
+            diagnostics.push(Diagnostic::error(
+                DiagnosticKind::MissingServerTrigger,
+                "Serving a flow requires a 'serverTrigger' node",
+            ));
+        }
+        diagnostics
+    }
+}
+
+impl WorkflowEngine {
+    /// Checks the flow against the node types registered on this engine.
+    pub fn validate(&self, flow: &Flow) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        let mut seen = HashSet::new();
+        for node in &flow.nodes {
+            if !seen.insert(node.id.as_str()) {
+                diagnostics.push(Diagnostic::error(DiagnosticKind::DuplicateNodeId, format!("Node id '{}' is used more than once", node.id)).node(&node.id));
+            }
+        }
+
+        for edge in &flow.edges {
+            for (field, id) in [("source", &edge.source), ("target", &edge.target)] {
+                if !seen.contains(id.as_str()) {
+                    diagnostics.push(
+                        Diagnostic::error(DiagnosticKind::DanglingEdge, format!("Edge {} points at missing node '{}'", field, id))
+                            .edge(&edge.id)
+                            .field(field),
+                    );
+                }
+            }
+        }
+
+        let mut names = HashSet::new();
+        for param in &flow.parameters {
+            let field = format!("parameters.{}", param.name);
+            if param.name.trim().is_empty() {
+                diagnostics.push(Diagnostic::error(DiagnosticKind::InvalidConfig, "Parameter names can't be empty").field("parameters"));
+            } else if !names.insert(param.name.as_str()) {
+                diagnostics.push(Diagnostic::error(DiagnosticKind::InvalidConfig, format!("Parameter '{}' is declared more than once", param.name)).field(field.clone()));
+            }
+            if let Some(Err(e)) = param.default.as_ref().map(|d| param.param_type.coerce(d)) {
+                diagnostics.push(Diagnostic::error(DiagnosticKind::InvalidConfig, format!("Default of parameter '{}' is invalid: {}", param.name, e)).field(format!("{}.default", field)));
+            }
+        }
+        let mut names = HashSet::new();
+        for output in &flow.outputs {
+            if output.name.trim().is_empty() {
+                diagnostics.push(Diagnostic::error(DiagnosticKind::InvalidConfig, "Output names can't be empty").field("outputs"));
+            } else if !names.insert(output.name.as_str()) {
+                diagnostics.push(Diagnostic::error(DiagnosticKind::InvalidConfig, format!("Output '{}' is declared more than once", output.name)).field(format!("outputs.{}", output.name)));
+            }
+        }
+
+        if flow.settings.max_visits == Some(0) {
+            diagnostics.push(Diagnostic::error(DiagnosticKind::InvalidConfig, "maxVisits must be at least 1").field("settings.maxVisits"));
+        }
+
+        // Settings the engine reads from any node, whatever its type.
+        for node in &flow.nodes {
+            if node.data.get("timeoutMs").is_some_and(|v| !v.is_u64()) {
+                diagnostics.push(
+                    Diagnostic::error(DiagnosticKind::InvalidConfig, "timeoutMs must be a whole number of milliseconds")
+                        .node(&node.id)
+                        .field("data.timeoutMs"),
+                );
+            }
+            if node.data.get("order").is_some_and(|v| !v.is_number()) {
+                diagnostics.push(
+                    Diagnostic::error(DiagnosticKind::InvalidConfig, "order must be a number")
+                        .node(&node.id)
+                        .field("data.order"),
+                );
+            }
+            if let Some(Err(e)) = node.data.get("retry").map(RetryPolicy::parse) {
+                diagnostics.push(
+                    Diagnostic::error(DiagnosticKind::InvalidConfig, format!("Invalid retry policy: {}", e))
+                        .node(&node.id)
+                        .field("data.retry"),
+                );
+            }
+        }
+
+        for node in &flow.nodes {
+            match self.executor(&node.node_type) {
+                None => diagnostics.push(
+                    Diagnostic::error(DiagnosticKind::UnknownNodeType, format!("Unknown node type '{}'", node.node_type))
+                        .node(&node.id)
+                        .field("type"),
+                ),
+                Some(executor) => {
+                    for issue in executor.validate_config(&node.data) {
+                        diagnostics.push(
+                            Diagnostic::error(DiagnosticKind::InvalidConfig, issue.message)
+                                .node(&node.id)
+                                .field(format!("data.{}", issue.field)),
+                        );
+                    }
+                }
+            }
+        }
+
+        let graph = Graph::new(flow);
+        for node_id in graph.unreachable() {
+            diagnostics.push(Diagnostic::warning(DiagnosticKind::UnreachableNode, "Node can never run: no path leads to it from a start node").node(node_id));
+        }
+        // A loop node only breaks the cycles that pass through it, so look for
+        // cycles in what is left once the loop nodes are taken out.
+        for mut members in graph.without_loops().cycles() {
+            members.sort();
+            diagnostics.push(
+                Diagnostic::error(DiagnosticKind::CycleWithoutLoop, format!("Cycle without a loop node: {}", members.join(", ")))
+                    .node(members[0]),
+            );
+        }
+
+        diagnostics
+    }
+}
+
+/// Adjacency view of a flow that ignores edges to missing nodes.
+struct Graph<'a> {
+    ids: Vec<&'a str>,
+    node_types: HashMap<&'a str, &'a str>,
+    adj: HashMap<&'a str, Vec<&'a str>>,
+    roots: Vec<&'a str>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(flow: &'a Flow) -> Self {
+        let ids: Vec<&str> = flow.nodes.iter().map(|n| n.id.as_str()).collect();
+        let node_types: HashMap<&str, &str> = flow.nodes.iter().map(|n| (n.id.as_str(), n.node_type.as_str())).collect();
+        let mut adj: HashMap<&str, Vec<&str>> = HashMap::new();
+        let mut has_incoming = HashSet::new();
+        for edge in &flow.edges {
+            if node_types.contains_key(edge.source.as_str()) && node_types.contains_key(edge.target.as_str()) {
+                adj.entry(edge.source.as_str()).or_default().push(edge.target.as_str());
+                has_incoming.insert(edge.target.as_str());
+            }
+        }
+        // Same entry points the engine starts from.
+        let roots = flow.nodes.iter()
+            .filter(|n| !has_incoming.contains(n.id.as_str()) || n.node_type == "start")
+            .map(|n| n.id.as_str())
+            .collect();
+        Self { ids, node_types, adj, roots }
+    }
+
+    /// The same graph with the loop nodes and their edges left out.
+    fn without_loops(&self) -> Graph<'a> {
+        let keep = |id: &str| self.node_types.get(id) != Some(&"loop");
+        Graph {
+            ids: self.ids.iter().copied().filter(|id| keep(id)).collect(),
+            node_types: self.node_types.iter().filter(|(id, _)| keep(id)).map(|(id, t)| (*id, *t)).collect(),
+            adj: self.adj.iter()
+                .filter(|(id, _)| keep(id))
+                .map(|(id, next)| (*id, next.iter().copied().filter(|n| keep(n)).collect()))
+                .collect(),
+            roots: self.roots.iter().copied().filter(|id| keep(id)).collect(),
+        }
+    }
+
+    fn unreachable(&self) -> Vec<&'a str> {
+        let mut reached: HashSet<&str> = HashSet::new();
+        let mut stack = self.roots.clone();
+        while let Some(id) = stack.pop() {
+            if reached.insert(id) {
+                stack.extend(self.adj.get(id).into_iter().flatten());
+            }
+        }
+        self.ids.iter().copied().filter(|id| !reached.contains(id)).collect()
+    }
+
+    /// Strongly connected components that contain a cycle (Tarjan's algorithm).
+    fn cycles(&self) -> Vec<Vec<&'a str>> {
+        struct State<'a> {
+            index: usize,
+            indices: HashMap<&'a str, usize>,
+            lowlink: HashMap<&'a str, usize>,
+            stack: Vec<&'a str>,
+            on_stack: HashSet<&'a str>,
+            components: Vec<Vec<&'a str>>,
+        }
+
+        fn visit<'a>(graph: &Graph<'a>, v: &'a str, st: &mut State<'a>) {
+            st.indices.insert(v, st.index);
+            st.lowlink.insert(v, st.index);
+            st.index += 1;
+            st.stack.push(v);
+            st.on_stack.insert(v);
+
+            for &w in graph.adj.get(v).into_iter().flatten() {
+                if !st.indices.contains_key(w) {
+                    visit(graph, w, st);
+                    let low = st.lowlink[v].min(st.lowlink[w]);
+                    st.lowlink.insert(v, low);
+                } else if st.on_stack.contains(w) {
+                    let low = st.lowlink[v].min(st.indices[w]);
+                    st.lowlink.insert(v, low);
+                }
+            }
+
+            if st.lowlink[v] == st.indices[v] {
+                let mut component = Vec::new();
+                while let Some(w) = st.stack.pop() {
+                    st.on_stack.remove(w);
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                st.components.push(component);
+            }
+        }
+
+        let mut st = State {
+            index: 0,
+            indices: HashMap::new(),
+            lowlink: HashMap::new(),
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            components: Vec::new(),
+        };
+        for &id in &self.ids {
+            if !st.indices.contains_key(id) {
+                visit(self, id, &mut st);
+            }
+        }
+
+        st.components.into_iter()
+            .filter(|c| c.len() > 1 || self.adj.get(c[0]).is_some_and(|n| n.contains(&c[0])))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(nodes: &[(&str, &str)], edges: &[(&str, &str)]) -> Flow {
+        let nodes: Vec<_> = nodes.iter().map(|(id, t)| serde_json::json!({ "id": id, "type": t, "data": {} })).collect();
+        let edges: Vec<_> = edges.iter().map(|(s, t)| serde_json::json!({ "id": format!("{}-{}", s, t), "source": s, "target": t })).collect();
+        Flow::from_json(&serde_json::json!({ "nodes": nodes, "edges": edges }).to_string()).unwrap()
+    }
+
+    fn cycle_messages(flow: &Flow) -> Vec<String> {
+        WorkflowEngine::new().validate(flow).into_iter()
+            .filter(|d| d.kind == DiagnosticKind::CycleWithoutLoop)
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn a_loop_node_only_covers_the_cycles_through_it() {
+        let nodes = [("l", "loop"), ("a", "input"), ("b", "input"), ("c", "input")];
+        // l -> a -> b -> l is broken by the loop node, but a <-> c never passes it.
+        let looped = flow(&nodes, &[("l", "a"), ("a", "b"), ("b", "l"), ("a", "c"), ("c", "a")]);
+        assert_eq!(cycle_messages(&looped), ["Cycle without a loop node: a, c"]);
+
+        let fine = flow(&nodes, &[("l", "a"), ("a", "b"), ("b", "l"), ("b", "c")]);
+        assert!(cycle_messages(&fine).is_empty());
+    }
+}
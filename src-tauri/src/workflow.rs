@@ -1,7 +1,22 @@
 use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use reqwest::Client;
+use futures::future::join_all;
+pub use async_trait::async_trait;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+use crate::checkpoint::{Checkpoint, CheckpointWriter, QueuedNode};
+use crate::debugger::Debugger;
+use crate::parameters::{FlowOutput, FlowParameter};
+use crate::retry::{self, Attempt, RetryPolicy};
+use crate::template::{self, Scope, TemplateError, TemplateOptions};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Position {
@@ -41,959 +56,1294 @@ pub struct Edge {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Flow {
+    /// Format version of the document; see [`crate::migration`].
+    #[serde(default)]
+    pub version: u32,
     pub nodes: Vec<Node>,
     pub edges: Vec<Edge>,
+    #[serde(default)]
+    pub settings: FlowSettings,
+    /// Variables the flow expects to be given; see [`Flow::bind_parameters`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameters: Vec<FlowParameter>,
+    /// Variables the flow sets for its caller.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<FlowOutput>,
+}
+
+/// Flow-wide execution settings stored next to the graph.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct FlowSettings {
+    /// Deadline for the whole run, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_ms: Option<u64>,
+    /// How many times a single node may run before the flow is stopped.
+    /// Defaults to [`DEFAULT_MAX_VISITS`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<usize>,
+    /// Fail a node when one of its `{{...}}` placeholders can't be resolved,
+    /// instead of leaving the placeholder in the text.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub strict_templates: bool,
+    /// Seed for the random values templates produce (`{{$uuid}}`,
+    /// `{{$randomInt(..)}}`, ...), making them the same on every run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+}
+
+/// Visit cap used when a flow doesn't set `settings.maxVisits`. It only
+/// exists to stop a runaway cycle, so it is far above what a loop needs.
+pub const DEFAULT_MAX_VISITS: usize = 10_000;
+
+/// Where a node stands in a run.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecutionStatus {
+    #[default]
+    Pending,
+    Running,
+    Success,
+    Error,
+    Skipped,
+    Cancelled,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl ExecutionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionStatus::Pending => "pending",
+            ExecutionStatus::Running => "running",
+            ExecutionStatus::Success => "success",
+            ExecutionStatus::Error => "error",
+            ExecutionStatus::Skipped => "skipped",
+            ExecutionStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl std::fmt::Display for ExecutionStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Outcome of one node run. Every field is always serialized so the GUI and
+/// CLI can rely on the shape; timestamps are milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionResult {
     pub node_id: String,
-    pub status: String, // "success", "error"
+    pub status: ExecutionStatus,
     pub output: serde_json::Value,
     pub error: Option<String>,
     pub active_handle: Option<String>,
+    /// Zero-based count of earlier runs of this node in the same execution.
+    #[serde(default)]
+    pub iteration: usize,
+    #[serde(default)]
+    pub attempt_count: u32,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub duration_ms: Option<u64>,
+    /// Every run of the node when it has a retry policy, oldest first.
+    #[serde(default)]
+    pub attempts: Vec<Attempt>,
 }
 
-pub struct WorkflowEngine {
-    client: Client,
-}
-
-impl WorkflowEngine {
-    pub fn new() -> Self {
+impl ExecutionResult {
+    fn new(node_id: impl Into<String>, status: ExecutionStatus, output: serde_json::Value) -> Self {
         Self {
-            client: Client::new(),
+            node_id: node_id.into(),
+            status,
+            output,
+            error: None,
+            active_handle: None,
+            iteration: 0,
+            attempt_count: 0,
+            started_at: None,
+            finished_at: None,
+            duration_ms: None,
+            attempts: Vec::new(),
         }
     }
 
-    pub async fn execute(&self, flow: &Flow, initial_variables: HashMap<String, serde_json::Value>) -> Result<(HashMap<String, ExecutionResult>, HashMap<String, serde_json::Value>)> {
-        let mut results: HashMap<String, ExecutionResult> = HashMap::new();
-        // ... same initialization ...
-        let mut node_map: HashMap<String, &Node> = HashMap::new();
-        let mut adj_list: HashMap<String, Vec<(&Edge, String)>> = HashMap::new();
-        let mut rev_adj_list: HashMap<String, Vec<String>> = HashMap::new();
+    pub fn success(node_id: impl Into<String>, output: serde_json::Value) -> Self {
+        Self::new(node_id, ExecutionStatus::Success, output)
+    }
 
-        for node in &flow.nodes {
-            node_map.insert(node.id.clone(), node);
-            adj_list.insert(node.id.clone(), Vec::new());
-            rev_adj_list.insert(node.id.clone(), Vec::new());
+    pub fn error(node_id: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::new(node_id, ExecutionStatus::Error, serde_json::Value::Null).with_error(message)
+    }
+
+    pub fn skipped(node_id: impl Into<String>, output: serde_json::Value) -> Self {
+        Self::new(node_id, ExecutionStatus::Skipped, output)
+    }
+
+    pub fn cancelled(node_id: impl Into<String>) -> Self {
+        Self::new(node_id, ExecutionStatus::Cancelled, serde_json::Value::Null).with_error("Cancelled")
+    }
+
+    pub fn with_output(mut self, output: serde_json::Value) -> Self {
+        self.output = output;
+        self
+    }
+
+    pub fn with_error(mut self, message: impl Into<String>) -> Self {
+        self.error = Some(message.into());
+        self
+    }
+
+    /// Only edges leaving from `handle` fire after this result.
+    pub fn with_handle(mut self, handle: impl Into<String>) -> Self {
+        self.active_handle = Some(handle.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.status == ExecutionStatus::Error
+    }
+
+    /// Whether `edge`, leaving the node that produced this result, fires.
+    pub fn fires(&self, edge: &Edge) -> bool {
+        match &self.active_handle {
+            Some(handle) => edge.source_handle.as_deref() == Some(handle),
+            None => edge.source_handle.as_deref() != Some(ERROR_HANDLE),
         }
+    }
+}
 
-        for edge in &flow.edges {
-            if let Some(neighbors) = adj_list.get_mut(&edge.source) {
-                neighbors.push((edge, edge.target.clone()));
-            }
-            if let Some(parents) = rev_adj_list.get_mut(&edge.target) {
-                parents.push(edge.source.clone());
-            }
+/// Everything a run produced.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlowRun {
+    /// The latest result of each node that ran.
+    pub results: HashMap<String, ExecutionResult>,
+    pub variables: HashMap<String, serde_json::Value>,
+    /// Every node run in the order it finished, so loop bodies keep one entry
+    /// per iteration (see [`ExecutionResult::iteration`]).
+    pub trace: Vec<ExecutionResult>,
+    /// Final values of the flow's declared `outputs` that were set.
+    #[serde(default)]
+    pub outputs: HashMap<String, serde_json::Value>,
+}
+
+/// How a node with several incoming edges decides when to run.
+/// Configured per node through `data.join`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinMode {
+    /// Run every time a parent dispatches to the node (the default).
+    Each,
+    /// Wait until every distinct parent has dispatched, then run once.
+    All,
+    /// Run on the first parent to arrive and absorb the others until a new round starts.
+    First,
+}
+
+impl JoinMode {
+    pub fn for_node(node: &Node) -> Self {
+        match node.data.get("join").and_then(|v| v.as_str()) {
+            Some("all") => JoinMode::All,
+            Some("first") => JoinMode::First,
+            _ => JoinMode::Each,
         }
+    }
+}
 
-        let mut queue: Vec<String> = flow.nodes.iter()
-            .filter(|n| {
-                 let incoming = rev_adj_list.get(&n.id).map(|v| v.len()).unwrap_or(0);
-                 incoming == 0 || n.node_type == "start"
-            })
-            .map(|n| n.id.clone())
-            .collect();
-            
-        queue.sort();
-        queue.dedup();
+/// The latest output of one parent, as seen through a single incoming edge.
+#[derive(Debug, Clone)]
+pub struct NodeInput {
+    pub source: String,
+    pub source_handle: Option<String>,
+    pub target_handle: Option<String>,
+    pub output: serde_json::Value,
+    /// True when this edge dispatched the current run of the node.
+    pub arrived: bool,
+}
 
-        let mut visited_count: HashMap<String, usize> = HashMap::new();
-        let mut variables: HashMap<String, serde_json::Value> = initial_variables;
+impl NodeInput {
+    /// The parent's `data` field, or its whole output when it has none.
+    pub fn data(&self) -> &serde_json::Value {
+        self.output.get("data").unwrap_or(&self.output)
+    }
 
-        while let Some(node_id) = queue.pop() {
-            *visited_count.entry(node_id.clone()).or_insert(0) += 1;
-            if *visited_count.get(&node_id).unwrap() > 10000 {
-                continue; 
-            }
+    /// Key under which this input is exposed: the target handle, else the parent id.
+    pub fn key(&self) -> &str {
+        self.target_handle.as_deref().unwrap_or(&self.source)
+    }
+}
 
-// ... (inside execute loop)
-
-                if let Some(node) = node_map.get(&node_id) {
-                    let result = self.execute_node(node, &results, &rev_adj_list, &mut variables).await;
-                    
-                    // ... result storage ...
-                    let res_clone = ExecutionResult {
-                        node_id: result.node_id.clone(),
-                        status: result.status.clone(),
-                        output: result.output.clone(),
-                        error: result.error.clone(),
-                        active_handle: result.active_handle.clone(),
-                    };
-                    results.insert(node_id.clone(), res_clone);
+/// Everything a node receives from its parents for one run. Edges that
+/// dispatched the run come first, in arrival order, followed by the
+/// remaining parents that already have a result.
+#[derive(Debug, Clone, Default)]
+pub struct NodeInputs {
+    entries: Vec<NodeInput>,
+    ports: &'static [&'static str],
+}
 
-                    // Re-queue loop node if it's continuing
-                    if node.node_type == "loop" {
-                        if let Some(handle) = &result.active_handle {
-                            if handle == "body" {
-                                queue.push(node_id.clone());
-                            }
-                        }
-                    }
+impl NodeInputs {
+    pub fn iter(&self) -> impl Iterator<Item = &NodeInput> {
+        self.entries.iter()
+    }
 
-                    if let Some(neighbors) = adj_list.get(&node_id) {
-                        for (edge, neighbor_id) in neighbors {
-// ...
-                        let should_dispatch = match &result.active_handle {
-                            Some(handle) => edge.source_handle.as_deref() == Some(handle),
-                            None => true 
-                        };
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
 
-                        if should_dispatch {
-                            queue.push(neighbor_id.clone());
-                        }
-                    }
-                }
-            }
-        }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
 
-        Ok((results, variables))
-    }
-
-    async fn execute_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>, variables: &mut HashMap<String, serde_json::Value>) -> ExecutionResult {
-        match node.node_type.as_str() {
-            "condition" => self.execute_condition_node(node, prior_results, rev_adj),
-            "loop" => self.execute_loop_node(node, prior_results, rev_adj),
-            "capture" => self.execute_capture_node(node, prior_results, rev_adj, variables),
-            "counter" => self.execute_counter_node(node, prior_results, rev_adj, variables),
-            "input" => self.execute_input_node(node, variables),
-            "httpRequest" => self.execute_http_node(node, variables).await,
-            "debug" => self.execute_debug_node(node, prior_results, rev_adj),
-            "carousel" => self.execute_passthrough_node(node, prior_results, rev_adj), 
-            "start" | "output" | "comment" | "group" => self.execute_passthrough_node(node, prior_results, rev_adj),
-            "serverTrigger" => self.execute_server_trigger_node(node, variables),
-            "serverResponse" => self.execute_server_response_node(node, prior_results, rev_adj, variables),
-            "mapper" => self.execute_mapper_node(node, prior_results, rev_adj),
-            "scraper" => self.execute_scraper_node(node, prior_results, rev_adj),
-            "filter" => self.execute_filter_node(node, prior_results, rev_adj),
-            "arrayMap" => self.execute_array_map_node(node, prior_results, rev_adj),
-            "assert" => self.execute_assert_node(node, prior_results, rev_adj),
-
-            // Remainder are skipped or unknown
-             _ => ExecutionResult {
-                node_id: node.id.clone(),
-                status: "skipped".to_string(),
-                output: serde_json::json!({ "message": "Unknown node type" }),
-                error: None,
-                active_handle: None,
-            },
-        }
+    /// The default input that triggered this run (or the first available one).
+    /// Inputs wired to a named port are never considered here.
+    pub fn primary(&self) -> Option<&NodeInput> {
+        self.entries.iter().find(|input| !self.is_port(input))
     }
 
+    /// The input connected to the named port, preferring one that just arrived.
+    pub fn port(&self, name: &str) -> Option<&NodeInput> {
+        self.entries.iter().find(|input| input.target_handle.as_deref() == Some(name))
+    }
 
+    /// `data` of the named port, if anything is connected to it.
+    pub fn port_data(&self, name: &str) -> Option<serde_json::Value> {
+        self.port(name).map(|input| input.data().clone())
+    }
 
-    fn execute_scraper_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        let input_html = if let Some(parents) = rev_adj.get(&node.id) {
-            parents.first().and_then(|id| prior_results.get(id)).and_then(|res| {
-                res.output.get("data").and_then(|d| d.as_str()).or_else(|| res.output.as_str())
-            }).unwrap_or("")
-        } else {
-            ""
-        };
+    fn is_port(&self, input: &NodeInput) -> bool {
+        input.target_handle.as_deref().is_some_and(|handle| self.ports.contains(&handle))
+    }
 
-        if input_html.is_empty() {
-            return ExecutionResult {
-                node_id: node.id.clone(),
-                status: "success".to_string(),
-                output: serde_json::json!({ "items": [] }),
-                error: None,
-                active_handle: None,
-            };
-        }
+    /// `data` of the primary input, or `Null` when the node has no inputs.
+    pub fn primary_data(&self) -> serde_json::Value {
+        self.primary().map(|i| i.data().clone()).unwrap_or(serde_json::Value::Null)
+    }
 
-        let document = scraper::Html::parse_document(input_html);
-        let rules = node.data.get("rules").and_then(|r| r.as_array());
-        
-        // Universal Scraping: List or Single Item
-        // If "container_selector" is present, we extract multiple items.
-        let container_selector_str = node.data.get("container_selector").and_then(|s| s.as_str()).unwrap_or("");
-        
-        let mut results = Vec::new();
-
-        if !container_selector_str.is_empty() {
-             if let Ok(selector) = scraper::Selector::parse(container_selector_str) {
-                 for element in document.select(&selector) {
-                     let mut item = serde_json::Map::new();
-                     if let Some(extraction_rules) = rules {
-                         for rule in extraction_rules {
-                             let sel_str = rule.get("selector").and_then(|s| s.as_str()).unwrap_or("");
-                             let attr = rule.get("attribute").and_then(|s| s.as_str()).unwrap_or("text");
-                             let key = rule.get("key").and_then(|s| s.as_str()).unwrap_or("value");
-                             
-                             if let Ok(inner_sel) = scraper::Selector::parse(sel_str) {
-                                  if let Some(inner_el) = element.select(&inner_sel).next() {
-                                      let val = if attr == "text" {
-                                          inner_el.text().collect::<Vec<_>>().join(" ").trim().to_string()
-                                      } else {
-                                          inner_el.value().attr(attr).unwrap_or("").to_string()
-                                      };
-                                      item.insert(key.to_string(), serde_json::json!(val));
-                                  }
-                             }
-                         }
-                     }
-                     if !item.is_empty() {
-                        results.push(serde_json::Value::Object(item));
-                     }
-                 }
-             }
-        } else {
-            // Single object extraction from root
-            let mut item = serde_json::Map::new();
-            if let Some(extraction_rules) = rules {
-                for rule in extraction_rules {
-                    let sel_str = rule.get("selector").and_then(|s| s.as_str()).unwrap_or("");
-                    let attr = rule.get("attribute").and_then(|s| s.as_str()).unwrap_or("text");
-                    let key = rule.get("key").and_then(|s| s.as_str()).unwrap_or("value");
-                    
-                    if let Ok(sel) = scraper::Selector::parse(sel_str) {
-                         if let Some(el) = document.select(&sel).next() {
-                             let val = if attr == "text" {
-                                 el.text().collect::<Vec<_>>().join(" ").trim().to_string()
-                             } else {
-                                 el.value().attr(attr).unwrap_or("").to_string()
-                             };
-                             item.insert(key.to_string(), serde_json::json!(val));
-                         }
-                    }
-                }
-            }
-            if !item.is_empty() {
-                results.push(serde_json::Value::Object(item));
-            }
+    /// All parent outputs keyed by handle, for nodes that merge several branches.
+    pub fn keyed(&self) -> serde_json::Map<String, serde_json::Value> {
+        let mut map = serde_json::Map::new();
+        for input in &self.entries {
+            map.entry(input.key().to_string()).or_insert_with(|| input.data().clone());
         }
+        map
+    }
+}
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "items": results, "data": results }),
-            error: None,
-            active_handle: None,
-        }
+/// A problem with a node's `data`, reported by [`NodeExecutor::validate_config`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConfigIssue {
+    pub field: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { field: field.into(), message: message.into() }
     }
+}
 
-    fn execute_filter_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        let input_val = if let Some(parents) = rev_adj.get(&node.id) {
-             parents.first().and_then(|id| prior_results.get(id)).and_then(|res| {
-                 res.output.get("data").or_else(|| Some(&res.output))
-             })
-        } else {
-            None
-        };
+/// Everything an executor gets to work with for one run of a node.
+pub struct NodeContext<'a> {
+    pub node: &'a Node,
+    pub inputs: &'a NodeInputs,
+    /// This node's result from its previous run, if it already ran (loops advance from it).
+    pub previous: Option<&'a ExecutionResult>,
+    /// The latest result of every node that ran before this wave, for `{{nodes.<id>...}}`.
+    pub results: &'a HashMap<String, ExecutionResult>,
+    /// Strict mode and the random generator of the run.
+    pub templates: &'a TemplateOptions,
+    /// The node's view of the flow variables; writes are merged back after the wave.
+    pub variables: &'a mut HashMap<String, serde_json::Value>,
+    pub client: &'a Client,
+    /// The engine running the node, for executors that run flows of their own.
+    pub engine: &'a WorkflowEngine,
+    /// Cancelled when the run is stopped or times out; flows a node runs of
+    /// its own should stop with it (see [`CancellationToken::child_token`]).
+    pub cancel: &'a CancellationToken,
+}
 
-        if input_val.is_none() {
-             return ExecutionResult {
-                node_id: node.id.clone(),
-                status: "success".to_string(),
-                output: serde_json::json!({ "items": [], "total": 0, "data": [] }),
-                error: None,
-                active_handle: None,
-            };
-        }
+impl NodeContext<'_> {
+    /// Renders the `{{...}}` placeholders in `text` against the current
+    /// variables and node results; see [`template`](crate::template).
+    pub fn render(&self, text: &str) -> Result<String, TemplateError> {
+        template::render(text, &self.scope(), self.templates.strict)
+    }
 
-        let val = input_val.unwrap();
-        let array = if let Some(arr) = val.as_array() {
-            arr.clone()
-        } else {
-            vec![val.clone()]
-        };
+    /// Renders the placeholders inside a JSON value, keeping the type of
+    /// values that are a placeholder on their own; see [`template::render_json`].
+    pub fn render_json(&self, value: &serde_json::Value) -> Result<serde_json::Value, TemplateError> {
+        template::render_json(value, &self.scope(), self.templates.strict)
+    }
 
-        let property = node.data.get("property").and_then(|p| p.as_str()).unwrap_or("");
-        let condition = node.data.get("condition").and_then(|c| c.as_str()).unwrap_or("equals");
-        let query_value = node.data.get("value").and_then(|v| v.as_str()).unwrap_or("");
+    /// What templates rendered for this node can refer to.
+    pub fn scope(&self) -> Scope<'_> {
+        Scope::new(self.variables).with_results(self.results).with_rng(&self.templates.rng)
+    }
+}
 
-        let mut filtered = Vec::new();
-        for item in array {
-            let item_prop = if property.is_empty() {
-                Some(item.clone())
-            } else {
-                item.get(property).cloned()
-            };
+/// Implements one node type. Register implementations on a [`WorkflowEngine`]
+/// with [`WorkflowEngine::register`] to add custom node types without touching
+/// the engine itself.
+#[async_trait]
+pub trait NodeExecutor: Send + Sync {
+    async fn execute(&self, ctx: NodeContext<'_>) -> ExecutionResult;
 
-            let matches = match condition {
-                "exists" => item_prop.is_some() && !item_prop.as_ref().unwrap().is_null(),
-                "notExists" => item_prop.is_none() || item_prop.as_ref().unwrap().is_null(),
-                _ => {
-                    let item_str = item_prop.map(|v| {
-                        if v.is_string() { v.as_str().unwrap().to_string() } else { v.to_string() }
-                    }).unwrap_or_else(|| "".to_string());
-
-                    match condition {
-                        "equals" => item_str == query_value,
-                        "notEquals" => item_str != query_value,
-                        "contains" => item_str.contains(query_value),
-                        "regex" => {
-                            if let Ok(re) = regex::Regex::new(query_value) {
-                                re.is_match(&item_str)
-                            } else {
-                                false
-                            }
-                        },
-                        "extension" => {
-                            let exts: Vec<&str> = query_value.split(',').map(|s| s.trim()).collect();
-                            exts.iter().any(|&e| item_str.ends_with(e))
-                        },
-                        _ => false,
-                    }
-                }
-            };
+    /// Named input ports besides the default input, matched against `Edge.target_handle`.
+    fn input_ports(&self) -> &'static [&'static str] {
+        &[]
+    }
 
-            if matches {
-                filtered.push(item);
-            }
-        }
+    /// JSON Schema describing what the node expects on its default input.
+    fn input_schema(&self) -> serde_json::Value {
+        serde_json::json!({})
+    }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "items": filtered, "total": filtered.len(), "data": filtered }),
-            error: None,
-            active_handle: None,
-        }
+    /// JSON Schema describing the `output` of a successful run.
+    fn output_schema(&self) -> serde_json::Value {
+        serde_json::json!({})
     }
 
-    fn execute_array_map_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        let input_val = if let Some(parents) = rev_adj.get(&node.id) {
-             parents.first().and_then(|id| prior_results.get(id)).and_then(|res| {
-                 res.output.get("data").or_else(|| Some(&res.output))
-             })
-        } else {
-            None
-        };
+    /// Checks the node's `data` before it runs. Any issue turns the run into an error.
+    fn validate_config(&self, _data: &serde_json::Value) -> Vec<ConfigIssue> {
+        Vec::new()
+    }
 
-        if input_val.is_none() {
-             return ExecutionResult {
-                node_id: node.id.clone(),
-                status: "success".to_string(),
-                output: serde_json::json!({ "items": [], "total": 0, "data": [] }),
-                error: None,
-                active_handle: None,
-            };
-        }
+    /// Whether the node changes `variables`. Such nodes never share a batch,
+    /// so a read-modify-write can't lose an update made alongside it.
+    fn writes_variables(&self) -> bool {
+        false
+    }
+}
 
-        let val = input_val.unwrap();
-        let array = if let Some(arr) = val.as_array() {
-            arr.clone()
-        } else {
-            vec![val.clone()]
+/// Progress reported while a flow runs, in the order things happen.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecutionEvent {
+    NodeStarted { node_id: String, node_type: String },
+    NodeFinished { result: ExecutionResult },
+    /// An edge carried control from `source` to `target`.
+    EdgeFired { edge_id: String, source: String, target: String },
+    VariableChanged { name: String, value: serde_json::Value },
+    /// The attached debugger stopped the run in front of `node_id`.
+    Paused { node_id: String },
+    FlowFinished { status: ExecutionStatus, node_count: usize },
+}
+
+/// Receives [`ExecutionEvent`]s from a running engine. Any
+/// `Fn(&ExecutionEvent) + Send + Sync` closure is an observer.
+pub trait ExecutionObserver: Send + Sync {
+    fn on_event(&self, event: &ExecutionEvent);
+}
+
+impl<F: Fn(&ExecutionEvent) + Send + Sync> ExecutionObserver for F {
+    fn on_event(&self, event: &ExecutionEvent) {
+        self(event)
+    }
+}
+
+/// Source handle every node fires, instead of its normal outputs, when it fails.
+/// Inputs arriving over it carry `{ "error": <message>, "output": <partial output> }`.
+pub const ERROR_HANDLE: &str = "error";
+
+/// Upper bound on how many ready nodes run at the same time unless overridden.
+pub const DEFAULT_MAX_CONCURRENCY: usize = 8;
+
+/// A node that is ready to run, together with the edges that dispatched it.
+type Ready<'a> = (String, Vec<&'a Edge>);
+
+pub struct WorkflowEngine {
+    client: Client,
+    max_concurrency: usize,
+    executors: HashMap<String, Arc<dyn NodeExecutor>>,
+    observer: Option<Arc<dyn ExecutionObserver>>,
+    flow_timeout: Option<Duration>,
+    node_timeout: Option<Duration>,
+    debugger: Option<Debugger>,
+    checkpoint_path: Option<PathBuf>,
+    /// Files of the flows being run, outermost first: the flow itself (when
+    /// known) and every subflow that led to this engine.
+    flow_files: Vec<PathBuf>,
+    seed: Option<u64>,
+}
+
+impl WorkflowEngine {
+    /// Creates an engine with all built-in node types registered.
+    pub fn new() -> Self {
+        let mut engine = Self {
+            client: Client::new(),
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            executors: HashMap::new(),
+            observer: None,
+            flow_timeout: None,
+            node_timeout: None,
+            debugger: None,
+            checkpoint_path: None,
+            flow_files: Vec::new(),
+            seed: None,
         };
+        crate::nodes::register_builtins(&mut engine);
+        engine
+    }
 
-        let path = node.data.get("path").and_then(|p| p.as_str()).unwrap_or("");
-        
-        let mut results = Vec::new();
-        for item in array {
-            let mut extracted = item.clone();
-            if !path.is_empty() {
-                for part in path.split('.') {
-                    if let Some(val) = extracted.get(part) {
-                        extracted = val.clone();
-                    } else {
-                        extracted = serde_json::Value::Null;
-                        break;
-                    }
-                }
-            }
-            if !extracted.is_null() {
-                results.push(extracted);
-            }
-        }
+    /// Deadline for a whole run, overriding the flow's own `settings.timeoutMs`.
+    pub fn with_flow_timeout(mut self, timeout: Duration) -> Self {
+        self.flow_timeout = Some(timeout);
+        self
+    }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "items": results, "total": results.len(), "data": results }),
-            error: None,
-            active_handle: None,
-        }
+    /// Time limit for nodes that don't set their own `data.timeoutMs`.
+    pub fn with_node_timeout(mut self, timeout: Duration) -> Self {
+        self.node_timeout = Some(timeout);
+        self
     }
 
-    fn substitute(&self, text: &str, vars: &HashMap<String, serde_json::Value>) -> String {
-        let mut result = text.to_string();
-        for (key, val) in vars {
-            let placeholder = format!("{{{{{}}}}}", key);
-            if result.contains(&placeholder) {
-                let val_str = if val.is_string() {
-                    val.as_str().unwrap().to_string()
-                } else {
-                    val.to_string()
-                };
-                result = result.replace(&placeholder, &val_str);
-            }
+    /// Streams execution progress to `observer` during [`WorkflowEngine::execute`].
+    pub fn with_observer(mut self, observer: impl ExecutionObserver + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Runs nodes one at a time and lets `debugger` pause before them.
+    pub fn with_debugger(mut self, debugger: Debugger) -> Self {
+        self.debugger = Some(debugger);
+        self
+    }
+
+    /// Saves a [`Checkpoint`] to `path` after every batch of nodes (every node
+    /// when running one at a time), so a failed run can be resumed. A checkpoint
+    /// that can't be saved is reported on stderr and the run goes on without.
+    pub fn with_checkpoints(mut self, path: impl Into<PathBuf>) -> Self {
+        self.checkpoint_path = Some(path.into());
+        self
+    }
+
+    /// The file the flow was loaded from. Subflow paths are resolved against its
+    /// directory, and a subflow that leads back to it is reported as recursion.
+    pub fn with_flow_file(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        self.flow_files = vec![fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())];
+        self
+    }
+
+    /// Seeds the random values templates produce, overriding the flow's own `settings.seed`.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Loads the flow a `subflow` node points at, together with the engine to
+    /// run it on. The child engine shares this engine's node types and limits
+    /// but reports nothing to the observer, debugger or checkpoint file, which
+    /// describe the outer run. Relative paths are resolved against the calling
+    /// flow's directory, or the working directory when it isn't known.
+    pub fn load_subflow(&self, path: &str) -> Result<(WorkflowEngine, Flow)> {
+        let path = match self.flow_files.last().and_then(|caller| caller.parent()) {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        let file = fs::canonicalize(&path).with_context(|| format!("Subflow {:?} not found", path))?;
+        if self.flow_files.contains(&file) {
+            let chain: Vec<String> = self.flow_files.iter().chain([&file]).map(|f| f.display().to_string()).collect();
+            bail!("Subflow recursion: {}", chain.join(" -> "));
         }
-        result
-    }
-
-    fn execute_input_node(&self, node: &Node, variables: &HashMap<String, serde_json::Value>) -> ExecutionResult {
-        let raw_val = node.data.get("value").and_then(|v| v.as_str()).unwrap_or("");
-        let val_str = self.substitute(raw_val, variables);
-        let val_type = node.data.get("type").and_then(|v| v.as_str()).unwrap_or("string");
-        
-        let output_val = match val_type {
-            "number" => {
-                if let Ok(num) = val_str.parse::<f64>() {
-                    serde_json::json!(num)
-                } else {
-                    serde_json::Value::String(val_str)
-                }
-            },
-            "json" => {
-                if let Ok(json_val) = serde_json::from_str::<serde_json::Value>(&val_str) {
-                    json_val
-                } else {
-                    serde_json::Value::String(val_str)
-                }
-            },
-            _ => serde_json::Value::String(val_str),
+        let data = fs::read_to_string(&file).with_context(|| format!("Failed to read subflow {:?}", file))?;
+        let flow = Flow::from_json(&data).with_context(|| format!("Failed to parse subflow {:?}", file))?;
+
+        let mut flow_files = self.flow_files.clone();
+        flow_files.push(file);
+        let engine = WorkflowEngine {
+            client: self.client.clone(),
+            max_concurrency: self.max_concurrency,
+            executors: self.executors.clone(),
+            observer: None,
+            flow_timeout: None,
+            node_timeout: self.node_timeout,
+            debugger: None,
+            checkpoint_path: None,
+            flow_files,
+            seed: None,
         };
+        Ok((engine, flow))
+    }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "data": output_val }),
-            error: None,
-            active_handle: None,
+    fn emit(&self, event: ExecutionEvent) {
+        if let Some(observer) = &self.observer {
+            observer.on_event(&event);
         }
     }
 
-    fn execute_condition_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        let start_val = serde_json::Value::Null;
-        let mut input_val = &start_val;
-        
-        if let Some(parents) = rev_adj.get(&node.id) {
-            if let Some(first_parent) = parents.first() {
-                if let Some(parent_res) = prior_results.get(first_parent) {
-                    if let Some(d) = parent_res.output.get("data") {
-                        input_val = d;
-                    } else {
-                        input_val = &parent_res.output;
-                    }
-                }
-            }
+    /// Registers (or replaces) the executor used for nodes of `node_type`.
+    pub fn register(&mut self, node_type: impl Into<String>, executor: impl NodeExecutor + 'static) {
+        self.executors.insert(node_type.into(), Arc::new(executor));
+    }
+
+    /// The executor registered for `node_type`, if any.
+    pub fn executor(&self, node_type: &str) -> Option<&dyn NodeExecutor> {
+        self.executors.get(node_type).map(|e| e.as_ref())
+    }
+
+    /// All registered node types, sorted.
+    pub fn node_types(&self) -> Vec<&str> {
+        let mut types: Vec<&str> = self.executors.keys().map(|k| k.as_str()).collect();
+        types.sort();
+        types
+    }
+
+    /// Limits how many independent nodes are executed concurrently.
+    /// A value of 1 runs the flow strictly one node at a time.
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// Runs the flow and returns the latest result per node, the final variables
+    /// and the trace of every node run in the order they finished.
+    ///
+    /// Nodes are scheduled in waves: every node dispatched by the previous wave is
+    /// ready at the same time and runs concurrently (up to `max_concurrency`). A
+    /// node that [writes variables](NodeExecutor::writes_variables) runs in a batch
+    /// of its own, so the variables come out the same at any concurrency and do
+    /// not depend on which request happened to finish first.
+    ///
+    /// The order is fixed by the graph and the canvas, never by node ids or the
+    /// order edges were drawn in. Nodes are ranked topologically (a parent before
+    /// its children); nodes that are otherwise tied go by their `data.order`
+    /// number if they have one, then top to bottom and left to right on the
+    /// canvas, and only then by id. Every wave runs, and merges its writes, in
+    /// rank order. The wave dispatched last runs first, so a loop body finishes
+    /// before its loop node moves on to the next item.
+    ///
+    /// A node dispatched by several parents is gated by its [`JoinMode`]. A node
+    /// that fails only dispatches along edges from its [`ERROR_HANDLE`]; one that
+    /// succeeds dispatches along every other edge (or just its `active_handle`).
+    ///
+    /// Cancelling `cancel` stops the run: nodes still in flight (including their
+    /// HTTP requests) are dropped and reported with status "cancelled", nothing
+    /// further is scheduled, and the results gathered so far are returned.
+    /// Hitting the flow timeout stops the run the same way, except that the
+    /// interrupted nodes are reported as errors naming the timeout.
+    ///
+    /// `initial_variables` are first checked against the flow's declared
+    /// parameters; a missing required one fails the run before any node starts.
+    pub async fn execute(&self, flow: &Flow, initial_variables: HashMap<String, serde_json::Value>, cancel: &CancellationToken) -> Result<FlowRun> {
+        let mut variables = initial_variables;
+        flow.bind_parameters(&mut variables)?;
+        let checkpoint = Checkpoint {
+            variables,
+            waves: vec![Self::roots(flow).into_iter().map(|node_id| QueuedNode { node_id, arrived: Vec::new() }).collect()],
+            ..Default::default()
+        };
+        self.resume(flow, checkpoint, cancel).await
+    }
+
+    /// Continues a run from a [`Checkpoint`]. Nodes that had failed run again
+    /// first, then the saved queue is worked off as in [`WorkflowEngine::execute`].
+    /// Saved checkpoints leave out secret parameters; put them back into its
+    /// variables with [`Flow::bind_secret_parameters`] first.
+    pub async fn resume(&self, flow: &Flow, checkpoint: Checkpoint, cancel: &CancellationToken) -> Result<FlowRun> {
+        let mut node_map: HashMap<String, &Node> = HashMap::new();
+        let mut adj_list: HashMap<String, Vec<&Edge>> = HashMap::new();
+        let mut incoming: HashMap<String, Vec<&Edge>> = HashMap::new();
+        let mut edge_map: HashMap<&str, &Edge> = HashMap::new();
+
+        for node in &flow.nodes {
+            node_map.insert(node.id.clone(), node);
+            adj_list.insert(node.id.clone(), Vec::new());
+            incoming.insert(node.id.clone(), Vec::new());
         }
 
-        let condition = node.data.get("condition").and_then(|v| v.as_str()).unwrap_or("equal");
-        let target_val_str = node.data.get("targetValue").and_then(|v| v.as_str()).unwrap_or("");
-        
-        let input_str = input_val.as_str().map(|s| s.to_string()).unwrap_or_else(|| input_val.to_string());
-        
-        let is_true = if let (Some(iv), Ok(tv)) = (input_val.as_f64().or_else(|| input_str.parse::<f64>().ok()), target_val_str.parse::<f64>()) {
-            match condition {
-                "equal" => (iv - tv).abs() < f64::EPSILON,
-                "notEqual" => (iv - tv).abs() >= f64::EPSILON,
-                "greaterThan" => iv > tv,
-                "lessThan" => iv < tv,
-                _ => false,
+        for edge in &flow.edges {
+            if !node_map.contains_key(&edge.source) || !node_map.contains_key(&edge.target) {
+                continue;
             }
-        } else {
-            match condition {
-                "equal" => input_str == target_val_str,
-                "notEqual" => input_str != target_val_str,
-                "greaterThan" => input_str > target_val_str.to_string(),
-                "lessThan" => input_str < target_val_str.to_string(),
-                "contains" => input_str.contains(target_val_str),
-                _ => false,
+            edge_map.insert(edge.id.as_str(), edge);
+            if let Some(neighbors) = adj_list.get_mut(&edge.source) {
+                neighbors.push(edge);
             }
-        };
-        
-        let active = if is_true { "true" } else { "false" };
+            if let Some(parents) = incoming.get_mut(&edge.target) {
+                parents.push(edge);
+            }
+        }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "result": is_true, "input": input_val }),
-            error: None,
-            active_handle: Some(active.to_string()),
+        // Every wave, and every node's edges, follow the same fixed order.
+        let rank = Self::schedule_order(&node_map, &adj_list, &incoming);
+        for edges in adj_list.values_mut() {
+            edges.sort_by_key(|e| (rank[&e.target], e.id.as_str()));
         }
-    }
-    
-    fn execute_loop_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        // Iterate on previous output index.
-        let mut index = 0;
-        if let Some(prev) = prior_results.get(&node.id) {
-             index = prev.output.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize + 1;
+        for edges in incoming.values_mut() {
+            edges.sort_by_key(|e| (rank[&e.source], e.id.as_str()));
         }
 
-        // Get Input Array
-        let mut array_val = Vec::new();
-        if let Some(parents) = rev_adj.get(&node.id) {
-            for parent_id in parents {
-                if let Some(res) = prior_results.get(parent_id) {
-                    let val = res.output.get("data").unwrap_or(&res.output);
-                    if let Some(arr) = val.as_array() {
-                        if !arr.is_empty() {
-                            array_val = arr.clone();
-                            break; 
-                        }
-                    } else if let Some(arr) = res.output.as_array() {
-                         if !arr.is_empty() {
-                            array_val = arr.clone();
-                            break; 
-                        }
+        // Saved node and edge ids are resolved against this flow; ones it no longer has are dropped.
+        let edges = |ids: Vec<String>| -> Vec<&Edge> { ids.iter().filter_map(|id| edge_map.get(id.as_str()).copied()).collect() };
+        let queue = |nodes: Vec<QueuedNode>| -> Vec<Ready> {
+            nodes.into_iter()
+                .filter(|q| node_map.contains_key(&q.node_id))
+                .map(|q| (q.node_id, edges(q.arrived)))
+                .collect()
+        };
+
+        let Checkpoint { results, variables, trace, failed, current, requeued, arrivals, waves, pending_joins, visits, .. } = checkpoint;
+        let mut results: HashMap<String, ExecutionResult> = results;
+        let mut trace: Vec<ExecutionResult> = trace;
+        let mut variables: HashMap<String, serde_json::Value> = variables;
+        let mut visited_count: HashMap<String, usize> = visits;
+
+        // Each entry is a wave of nodes that became ready together. Waves are
+        // processed last-in-first-out so a loop body finishes before the loop
+        // node that scheduled it runs its next iteration. `current` holds the
+        // part of the popped wave that has not started yet.
+        let mut waves: Vec<Vec<Ready>> = waves.into_iter().map(&queue).collect();
+        let mut current: Vec<Ready> = queue(failed).into_iter().chain(queue(current)).collect();
+        let mut requeued: Vec<Ready> = queue(requeued);
+        let mut arrivals: Vec<Ready> = queue(arrivals);
+        let mut pending_joins: HashMap<String, Vec<&Edge>> = pending_joins.into_iter()
+            .map(|(node_id, arrived)| (node_id, edges(arrived)))
+            .collect();
+        let mut failed: Vec<Ready> = Vec::new();
+
+        let flow_timeout = self.flow_timeout.or(flow.settings.timeout_ms.map(Duration::from_millis));
+        let deadline = flow_timeout.map(|timeout| Instant::now() + timeout);
+        let max_visits = flow.settings.max_visits.unwrap_or(DEFAULT_MAX_VISITS);
+        let templates = TemplateOptions::new(flow.settings.strict_templates, self.seed.or(flow.settings.seed));
+        let templates = &templates;
+        let halted = || cancel.is_cancelled() || deadline.is_some_and(|d| Instant::now() >= d);
+        // What running nodes see: cancelled on stop and at the flow deadline.
+        let interrupt = cancel.child_token();
+        let interrupt = &interrupt;
+
+        let mut checkpoints = self.checkpoint_path.as_ref().map(CheckpointWriter::new);
+
+        // Stepping only makes sense when nodes run one after another.
+        let concurrency = if self.debugger.is_some() { 1 } else { self.max_concurrency };
+
+        loop {
+            if current.is_empty() {
+                if !requeued.is_empty() {
+                    waves.push(std::mem::take(&mut requeued));
+                }
+                let next = Self::resolve_joins(std::mem::take(&mut arrivals), &node_map, &incoming, &mut pending_joins);
+                if !next.is_empty() {
+                    waves.push(next);
+                }
+                match waves.pop() {
+                    Some(mut wave) => {
+                        wave.sort_by_key(|(node_id, _)| rank[node_id]);
+                        current = wave;
                     }
+                    None => break,
                 }
             }
-        }
-        
-        if index < array_val.len() {
-            let item = &array_val[index];
-            ExecutionResult {
-                node_id: node.id.clone(),
-                status: "success".to_string(),
-                output: serde_json::json!({ "index": index, "item": item, "data": item }),
-                error: None,
-                active_handle: Some("body".to_string()),
+            if halted() {
+                break;
             }
-        } else {
-             ExecutionResult {
-                node_id: node.id.clone(),
-                status: "success".to_string(),
-                output: serde_json::json!({ "status": "done", "index": index }),
-                error: None,
-                active_handle: Some("done".to_string()),
+
+            // Nodes that write variables run alone; the others run up to the next writer.
+            let writes = |(node_id, _): &Ready| node_map.get(node_id).and_then(|n| self.executor(&n.node_type)).is_some_and(|e| e.writes_variables());
+            let size = match current.iter().position(writes) {
+                Some(0) => 1,
+                Some(writer) => writer.min(concurrency),
+                None => concurrency.min(current.len()),
+            };
+
+            // A node past the visit cap is almost certainly stuck in a cycle; stop the
+            // whole run. The batch is checked before it is taken off the queue, so
+            // the last checkpoint still holds all of it for a resume.
+            let visits = |node_id: &str| visited_count.get(node_id).copied().unwrap_or(0);
+            if let Some((node_id, _)) = current[..size].iter().find(|(node_id, _)| visits(node_id) >= max_visits) {
+                let mut result = ExecutionResult::error(node_id.clone(), format!("Node '{}' exceeded the limit of {} visits", node_id, max_visits))
+                    .with_output(serde_json::json!({ "max_visits": max_visits }));
+                result.iteration = visits(node_id);
+                self.record(result, &mut results, &mut trace);
+                break;
             }
-        }
-    }
 
-    fn execute_passthrough_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        let mut input_val = serde_json::Value::Null;
-        
-        // Try to get data from first parent
-        if let Some(parents) = rev_adj.get(&node.id) {
-            if let Some(first_parent) = parents.first() {
-                if let Some(parent_res) = prior_results.get(first_parent) {
-                    if let Some(d) = parent_res.output.get("data") {
-                        input_val = d.clone();
-                    } else {
-                        input_val = parent_res.output.clone();
+            let batch: Vec<(&Node, Vec<&Edge>, usize)> = current.drain(..size)
+                .filter_map(|(node_id, arrived)| node_map.get(&node_id).map(|node| (*node, arrived)))
+                .map(|(node, arrived)| {
+                    let count = visited_count.entry(node.id.clone()).or_insert(0);
+                    *count += 1;
+                    (node, arrived, *count - 1)
+                })
+                .collect();
+
+            if let Some(debugger) = &self.debugger {
+                for (node, _, _) in &batch {
+                    if debugger.should_pause(node) {
+                        self.emit(ExecutionEvent::Paused { node_id: node.id.clone() });
+                        debugger.pause(node, &mut variables, &results, cancel).await;
                     }
                 }
+                if halted() {
+                    break;
+                }
             }
-        }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "status": "ok", "data": input_val }),
-            error: None,
-            active_handle: None,
-        }
-    }
+            let snapshot = variables.clone();
+            let prior_results = &results;
+            let outcomes = join_all(batch.iter().map(|(node, arrived, iteration)| {
+                let iteration = *iteration;
+                let inputs = self.collect_inputs(node, arrived, &incoming, prior_results);
+                let mut local_vars = snapshot.clone();
+                async move {
+                    self.emit(ExecutionEvent::NodeStarted { node_id: node.id.clone(), node_type: node.node_type.clone() });
+                    let started_at = SystemTime::now();
+                    let clock = Instant::now();
+                    let (mut result, completed) = tokio::select! {
+                        result = self.execute_node(node, &inputs, prior_results, templates, &mut local_vars, interrupt) => (result, true),
+                        _ = sleep_until(deadline) => {
+                            interrupt.cancel();
+                            let ms = flow_timeout.unwrap_or_default().as_millis();
+                            (Self::timed_out(node, format!("Flow timed out after {}ms", ms), ms), false)
+                        },
+                        _ = cancel.cancelled() => (ExecutionResult::cancelled(node.id.clone()), false),
+                    };
+                    result.iteration = iteration;
+                    result.attempt_count = result.attempt_count.max(1);
+                    result.started_at = Some(epoch_millis(started_at));
+                    result.finished_at = Some(epoch_millis(SystemTime::now()));
+                    result.duration_ms = Some(clock.elapsed().as_millis() as u64);
+                    // Whatever an interrupted node wrote before it was dropped is discarded.
+                    (*node, arrived.clone(), result, completed.then_some(local_vars))
+                }
+            })).await;
+
+            let mut interrupted = false;
+            for (node, arrived, mut result, local_vars) in outcomes {
+                // Nodes cut short by cancellation or the flow deadline dispatch nothing.
+                if local_vars.is_none() && halted() {
+                    interrupted = true;
+                    self.record(result, &mut results, &mut trace);
+                    continue;
+                }
 
-    async fn execute_http_node(&self, node: &Node, variables: &HashMap<String, serde_json::Value>) -> ExecutionResult {
-        let method = node.data.get("method").and_then(|v| v.as_str()).unwrap_or("GET");
-        let mut url = node.data.get("endpoint").and_then(|v| v.as_str()).unwrap_or("").to_string();
-        url = self.substitute(&url, variables);
+                // Apply only what this node changed; at most one node in a batch writes.
+                for (key, value) in local_vars.into_iter().flatten() {
+                    if snapshot.get(&key) != Some(&value) {
+                        self.emit(ExecutionEvent::VariableChanged { name: key.clone(), value: value.clone() });
+                        variables.insert(key, value);
+                    }
+                }
 
-        let mut builder = match method {
-            "GET" => self.client.get(&url),
-            "POST" => self.client.post(&url),
-            "PUT" => self.client.put(&url),
-            "DELETE" => self.client.delete(&url),
-            "PATCH" => self.client.patch(&url),
-            _ => self.client.get(&url),
-        };
+                // A failed node only continues down its error edges.
+                failed.retain(|(id, _)| *id != node.id);
+                if result.is_error() {
+                    result.active_handle = Some(ERROR_HANDLE.to_string());
+                    failed.push((node.id.clone(), arrived));
+                }
 
-        // Add Headers
-        if let Some(headers) = node.data.get("headers").and_then(|h| h.as_object()) {
-            for (k, v) in headers {
-                if let Some(v_str) = v.as_str() {
-                    let subbed_v = self.substitute(v_str, variables);
-                    builder = builder.header(k, subbed_v);
+                // Re-queue loop node if it's continuing
+                if node.node_type == "loop" && result.active_handle.as_deref() == Some("body") {
+                    requeued.push((node.id.clone(), Vec::new()));
                 }
-            }
-        }
 
-        // Add Body (if JSON)
-        if let Some(body) = node.data.get("body") {
-            if body.is_object() || body.is_array() {
-                let body_str = body.to_string();
-                let subbed_body_str = self.substitute(&body_str, variables);
-                if let Ok(parsed_body) = serde_json::from_str::<serde_json::Value>(&subbed_body_str) {
-                    builder = builder.json(&parsed_body);
-                } else {
-                     builder = builder.body(subbed_body_str);
+                if let Some(neighbors) = adj_list.get(&node.id) {
+                    for edge in neighbors {
+                        if result.fires(edge) {
+                            self.emit(ExecutionEvent::EdgeFired { edge_id: edge.id.clone(), source: edge.source.clone(), target: edge.target.clone() });
+                            match arrivals.iter_mut().find(|(id, _)| *id == edge.target) {
+                                Some((_, edges)) => edges.push(edge),
+                                None => arrivals.push((edge.target.clone(), vec![*edge])),
+                            }
+                        }
+                    }
                 }
+
+                self.record(result, &mut results, &mut trace);
             }
-        }
 
-        match builder.send().await {
-            Ok(res) => {
-                let status = res.status().as_u16();
-                let output = if res.headers().get("content-type").and_then(|h| h.to_str().ok()).map(|s| s.contains("json")).unwrap_or(false) {
-                    let body = res.json::<serde_json::Value>().await.ok();
-                     serde_json::json!({
-                        "status": status,
-                        "data": body
-                    })
-                } else {
-                    let text = res.text().await.unwrap_or_default();
-                    if let Ok(json_val) = serde_json::from_str::<serde_json::Value>(&text) {
-                         serde_json::json!({
-                            "status": status,
-                            "data": json_val
-                        })
-                    } else {
-                         serde_json::json!({
-                            "status": status,
-                            "data": text
-                        })
-                    }
+            // An interrupted batch is left out so resuming runs it again.
+            if let (Some(writer), false) = (&mut checkpoints, interrupted) {
+                let queued = |ready: &[Ready]| -> Vec<QueuedNode> {
+                    ready.iter()
+                        .map(|(node_id, arrived)| QueuedNode { node_id: node_id.clone(), arrived: arrived.iter().map(|e| e.id.clone()).collect() })
+                        .collect()
+                };
+                let checkpoint = Checkpoint {
+                    results: results.clone(),
+                    variables: variables.iter().filter(|(name, _)| !flow.is_secret(name)).map(|(k, v)| (k.clone(), v.clone())).collect(),
+                    failed: queued(&failed),
+                    current: queued(&current),
+                    requeued: queued(&requeued),
+                    arrivals: queued(&arrivals),
+                    waves: waves.iter().map(|w| queued(w)).collect(),
+                    pending_joins: pending_joins.iter().map(|(id, arrived)| (id.clone(), arrived.iter().map(|e| e.id.clone()).collect())).collect(),
+                    visits: visited_count.clone(),
+                    ..Default::default()
                 };
-                
-                ExecutionResult {
-                    node_id: node.id.clone(),
-                    status: "success".to_string(),
-                    output,
-                    error: None,
-                    active_handle: None,
+                if let Err(e) = writer.save(checkpoint, &trace) {
+                    eprintln!("Could not save checkpoint {:?}, continuing without: {:#}", writer.path(), e);
+                    checkpoints = None;
                 }
             }
-            Err(e) => ExecutionResult {
-                node_id: node.id.clone(),
-                status: "error".to_string(),
-                output: serde_json::Value::Null,
-                error: Some(e.to_string()),
-                active_handle: None,
-            }
         }
-    }
 
-    fn execute_debug_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        let mut debug_data = HashMap::new();
-        
-        if let Some(parents) = rev_adj.get(&node.id) {
-            for parent_id in parents {
-                if let Some(res) = prior_results.get(parent_id) {
-                    debug_data.insert(parent_id.clone(), res.output.clone());
-                }
+        // Nodes still waiting on a barrier never ran; report what they were missing.
+        for (node_id, arrived) in pending_joins {
+            if arrived.is_empty() || results.contains_key(&node_id) {
+                continue;
             }
+            let waiting_for: Vec<&str> = incoming.get(&node_id).into_iter().flatten()
+                .map(|e| e.source.as_str())
+                .filter(|source| !arrived.iter().any(|e| e.source == *source))
+                .collect();
+            let result = ExecutionResult::skipped(node_id.clone(), serde_json::json!({ "message": "Join incomplete", "waiting_for": waiting_for }));
+            self.record(result, &mut results, &mut trace);
         }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "debug": debug_data, "message": "Debug info captured" }), 
-            error: None,
-            active_handle: None,
-        }
+        let status = if cancel.is_cancelled() {
+            ExecutionStatus::Cancelled
+        } else if results.values().any(|r| r.is_error()) {
+            ExecutionStatus::Error
+        } else {
+            ExecutionStatus::Success
+        };
+        self.emit(ExecutionEvent::FlowFinished { status, node_count: results.len() });
+
+        let outputs = flow.collect_outputs(&variables);
+        Ok(FlowRun { results, variables, trace, outputs })
     }
 
-    fn execute_capture_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>, variables: &mut HashMap<String, serde_json::Value>) -> ExecutionResult {
-        let path = node.data.get("path").and_then(|v| v.as_str()).unwrap_or("");
-        let variable_name = node.data.get("variable").and_then(|v| v.as_str()).unwrap_or("");
+    /// Entry points: nodes without incoming edges, plus every `start` node.
+    fn roots(flow: &Flow) -> Vec<String> {
+        let ids: std::collections::HashSet<&str> = flow.nodes.iter().map(|n| n.id.as_str()).collect();
+        let mut roots: Vec<String> = flow.nodes.iter()
+            .filter(|n| {
+                let has_incoming = flow.edges.iter().any(|e| e.target == n.id && ids.contains(e.source.as_str()));
+                !has_incoming || n.node_type == "start"
+            })
+            .map(|n| n.id.clone())
+            .collect();
+        roots.sort();
+        roots.dedup();
+        roots
+    }
 
-        let mut input_val = &serde_json::Value::Null;
-         if let Some(parents) = rev_adj.get(&node.id) {
-            if let Some(first_parent) = parents.first() {
-                if let Some(parent_res) = prior_results.get(first_parent) {
-                    if let Some(d) = parent_res.output.get("data") {
-                        input_val = d;
-                    } else {
-                        input_val = &parent_res.output;
-                    }
+    /// Ranks every node for scheduling. The ranking is a topological order in
+    /// which, among nodes whose parents are all ranked, the one that comes first
+    /// by [`schedule_tie_break`] goes next. A cycle (a loop body feeding back
+    /// into its loop node) is entered at a node that already has a ranked parent.
+    fn schedule_order(node_map: &HashMap<String, &Node>, adj_list: &HashMap<String, Vec<&Edge>>, incoming: &HashMap<String, Vec<&Edge>>) -> HashMap<String, usize> {
+        let mut unranked_parents: HashMap<&str, usize> = incoming.iter().map(|(id, edges)| (id.as_str(), edges.len())).collect();
+        let mut rank: HashMap<String, usize> = HashMap::new();
+
+        while rank.len() < node_map.len() {
+            let pending: Vec<&Node> = node_map.values().filter(|n| !rank.contains_key(&n.id)).copied().collect();
+            let first = |ready: &dyn Fn(&Node) -> bool| pending.iter().filter(|n| ready(n)).min_by(|a, b| schedule_tie_break(a, b)).map(|n| n.id.as_str());
+            let next = first(&|n| unranked_parents[n.id.as_str()] == 0)
+                .or_else(|| first(&|n| incoming[&n.id].iter().any(|e| rank.contains_key(&e.source))))
+                .or_else(|| first(&|_| true));
+            let Some(next) = next else { break };
+
+            for edge in &adj_list[next] {
+                if let Some(count) = unranked_parents.get_mut(edge.target.as_str()) {
+                    *count = count.saturating_sub(1);
                 }
             }
+            rank.insert(next.to_string(), rank.len());
         }
+        rank
+    }
 
-        let mut extracted = input_val;
-        if !path.is_empty() {
-            for part in path.split('.') {
-                if let Some(val) = extracted.get(part) {
-                    extracted = val;
-                } else {
-                    extracted = &serde_json::Value::Null;
-                    break;
-                }
-            }
-        }
+    /// Publishes a finished node and keeps it both as the node's latest result
+    /// and as the next entry of the trace.
+    fn record(&self, result: ExecutionResult, results: &mut HashMap<String, ExecutionResult>, trace: &mut Vec<ExecutionResult>) {
+        self.emit(ExecutionEvent::NodeFinished { result: result.clone() });
+        results.insert(result.node_id.clone(), result.clone());
+        trace.push(result);
+    }
 
-        if !variable_name.is_empty() {
-            variables.insert(variable_name.to_string(), extracted.clone());
-        }
+    /// The node's own time limit from `data.timeoutMs`.
+    fn node_timeout(node: &Node) -> Option<Duration> {
+        node.data.get("timeoutMs").and_then(|v| v.as_u64()).map(Duration::from_millis)
+    }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "variable": variable_name, "data": extracted }),
-            error: None,
-            active_handle: None,
-        }
+    fn timed_out(node: &Node, message: String, timeout_ms: u128) -> ExecutionResult {
+        ExecutionResult::error(node.id.clone(), message).with_output(serde_json::json!({ "timeout_ms": timeout_ms }))
     }
 
-    fn execute_mapper_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        let mut input_val = &serde_json::Value::Null;
-        if let Some(parents) = rev_adj.get(&node.id) {
-            if let Some(first_parent) = parents.first() {
-                if let Some(parent_res) = prior_results.get(first_parent) {
-                    input_val = parent_res.output.get("data").unwrap_or(&parent_res.output);
+    /// Applies each target's join mode to the dispatches of one wave and
+    /// returns the nodes that may run in the next wave.
+    fn resolve_joins<'a>(arrivals: Vec<Ready<'a>>, node_map: &HashMap<String, &Node>, incoming: &HashMap<String, Vec<&'a Edge>>, pending_joins: &mut HashMap<String, Vec<&'a Edge>>) -> Vec<Ready<'a>> {
+        let mut next = Vec::new();
+
+        for (target, edges) in arrivals {
+            let Some(node) = node_map.get(&target) else { continue };
+            let parents: Vec<&str> = incoming.get(&target).into_iter().flatten().map(|e| e.source.as_str()).collect();
+            let covers_all = |arrived: &[&Edge]| parents.iter().all(|p| arrived.iter().any(|e| e.source == *p));
+
+            match JoinMode::for_node(node) {
+                JoinMode::Each => next.push((target, edges)),
+                JoinMode::All => {
+                    let pending = pending_joins.entry(target.clone()).or_default();
+                    // A parent that dispatches again before the barrier opens replaces its earlier arrival.
+                    pending.retain(|e| !edges.iter().any(|n| n.source == e.source));
+                    pending.extend(edges);
+                    if covers_all(pending) {
+                        next.push((target, std::mem::take(pending)));
+                    }
+                }
+                JoinMode::First => {
+                    let pending = pending_joins.entry(target.clone()).or_default();
+                    let new_round = pending.is_empty() || edges.iter().any(|n| pending.iter().any(|e| e.source == n.source));
+                    if new_round {
+                        pending.clear();
+                        next.push((target.clone(), edges.clone()));
+                    }
+                    pending.extend(edges);
+                    if covers_all(pending) {
+                        pending.clear();
+                    }
                 }
             }
         }
 
-        let mapping = node.data.get("mapping").and_then(|m| m.as_object());
-        let fallback = node.data.get("fallback").cloned().unwrap_or(serde_json::json!("Unknown"));
+        next
+    }
 
-        let key = if input_val.is_number() {
-            input_val.to_string()
-        } else if let Some(s) = input_val.as_str() {
-            s.to_string()
-        } else {
-            "".to_string()
-        };
+    fn collect_inputs(&self, node: &Node, arrived: &[&Edge], incoming: &HashMap<String, Vec<&Edge>>, prior_results: &HashMap<String, ExecutionResult>) -> NodeInputs {
+        let mut entries: Vec<NodeInput> = incoming.get(&node.id).into_iter().flatten()
+            .filter_map(|edge| {
+                prior_results.get(&edge.source).map(|res| NodeInput {
+                    source: edge.source.clone(),
+                    source_handle: edge.source_handle.clone(),
+                    target_handle: edge.target_handle.clone(),
+                    output: if edge.source_handle.as_deref() == Some(ERROR_HANDLE) {
+                        serde_json::json!({ "error": res.error, "output": res.output })
+                    } else {
+                        res.output.clone()
+                    },
+                    arrived: arrived.iter().any(|e| e.id == edge.id),
+                })
+            })
+            .collect();
 
-        let result = mapping
-            .and_then(|m| m.get(&key))
-            .cloned()
-            .unwrap_or(fallback);
+        entries.sort_by_key(|input| {
+            arrived.iter().position(|e| e.source == input.source && input.arrived).unwrap_or(usize::MAX)
+        });
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "data": result }),
-            error: None,
-            active_handle: None,
-        }
+        let ports = self.executor(&node.node_type).map(|e| e.input_ports()).unwrap_or(&[]);
+        NodeInputs { entries, ports }
     }
 
-    fn execute_counter_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>, variables: &mut HashMap<String, serde_json::Value>) -> ExecutionResult {
-        let variable_name = node.data.get("variable").and_then(|v| v.as_str()).unwrap_or("");
-        let operation = node.data.get("operation").and_then(|v| v.as_str()).unwrap_or("increment");
-        
-        // Amount can now be a string for substitution or a number
-        let default_amount = serde_json::json!(0.0);
-        let raw_amount = node.data.get("amount").unwrap_or(&default_amount);
-        let amount_str = if raw_amount.is_string() {
-            raw_amount.as_str().unwrap().to_string()
-        } else {
-            raw_amount.to_string()
+    async fn execute_node(&self, node: &Node, inputs: &NodeInputs, prior_results: &HashMap<String, ExecutionResult>, templates: &TemplateOptions, variables: &mut HashMap<String, serde_json::Value>, cancel: &CancellationToken) -> ExecutionResult {
+        let Some(executor) = self.executor(&node.node_type) else {
+            return ExecutionResult::error(node.id.clone(), format!("Unknown node type '{}'", node.node_type));
         };
-        
-        let subbed_amount = self.substitute(&amount_str, variables);
-        let amount_val = subbed_amount.parse::<f64>().unwrap_or(0.0);
-
-        if !variable_name.is_empty() {
-            let current_val = variables.get(variable_name).and_then(|v| v.as_f64()).unwrap_or(0.0);
-            
-            let new_val = match operation {
-                "increment" => serde_json::json!(current_val + amount_val),
-                "decrement" => serde_json::json!(current_val - amount_val),
-                "set" => serde_json::json!(amount_val),
-                "assign" => {
-                    // Get data from first parent
-                    let mut input_data = serde_json::Value::Null;
-                    if let Some(parents) = rev_adj.get(&node.id) {
-                        if let Some(first_parent) = parents.first() {
-                            if let Some(parent_res) = prior_results.get(first_parent) {
-                                input_data = parent_res.output.get("data").cloned().unwrap_or(parent_res.output.clone());
-                            }
-                        }
-                    }
-                    input_data
-                },
-                "append" => {
-                    // Logic for append (existing)
-                    // ... (keep append logic above or copy it if needed context)
-                    // Wait, I need to match the existing context properly. 
-                    // To be safe, I will replace the whole block or append new cases.
-                    // Let's rewrite the append block and new ones.
-                    
-                    let input_data = self.get_first_parent_data(node, prior_results, rev_adj);
-                    
-                    let mut arr = self.get_variable_as_array(variables, variable_name);
-                    
-                    if !input_data.is_null() {
-                        arr.push(input_data);
-                    }
-                    
-                    serde_json::Value::Array(arr)
-                },
-                "prepend" => {
-                    let input_data = self.get_first_parent_data(node, prior_results, rev_adj);
-                    let mut arr = self.get_variable_as_array(variables, variable_name);
-                    
-                    if !input_data.is_null() {
-                        arr.insert(0, input_data);
-                    }
-                    
-                    serde_json::Value::Array(arr)
-                },
-                "pop" => {
-                    let mut arr = self.get_variable_as_array(variables, variable_name);
-                    arr.pop();
-                    serde_json::Value::Array(arr)
-                },
-                "shift" => {
-                    let mut arr = self.get_variable_as_array(variables, variable_name);
-                    if !arr.is_empty() {
-                        arr.remove(0);
-                    }
-                    serde_json::Value::Array(arr)
-                },
-                _ => serde_json::json!(current_val),
-            };
-            
-            variables.insert(variable_name.to_string(), new_val);
-        }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({ "variable": variable_name, "status": "updated" }),
-            error: None,
-            active_handle: None,
+        let issues = executor.validate_config(&node.data);
+        if !issues.is_empty() {
+            let details: Vec<String> = issues.iter().map(|i| format!("{}: {}", i.field, i.message)).collect();
+            return ExecutionResult::error(node.id.clone(), format!("Invalid configuration: {}", details.join("; ")))
+                .with_output(serde_json::json!({ "issues": issues }));
         }
-    }
 
-    fn execute_assert_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> ExecutionResult {
-        // 1. Get Input
-        let mut input_val = &serde_json::Value::Null;
-        if let Some(parents) = rev_adj.get(&node.id) {
-            if let Some(first_parent) = parents.first() {
-                if let Some(parent_res) = prior_results.get(first_parent) {
-                    if let Some(d) = parent_res.output.get("data") {
-                        input_val = d;
-                    } else {
-                        input_val = &parent_res.output;
+        let previous = prior_results.get(&node.id);
+        let node_timeout = Self::node_timeout(node).or(self.node_timeout);
+        let policy = RetryPolicy::for_node(node);
+
+        // Each attempt starts from the same variables and has the whole node
+        // timeout to itself. Only the last attempt's writes are kept, and only
+        // if it finished in time.
+        let mut attempts = Vec::new();
+        loop {
+            let mut attempt_vars = variables.clone();
+            let started = Instant::now();
+            let ctx = NodeContext { node, inputs, previous, results: prior_results, templates, variables: &mut attempt_vars, client: &self.client, engine: self, cancel };
+            let (mut result, finished) = match node_timeout {
+                Some(limit) => match tokio::time::timeout(limit, executor.execute(ctx)).await {
+                    Ok(result) => (result, true),
+                    Err(_) => (Self::timed_out(node, format!("Node timed out after {}ms", limit.as_millis()), limit.as_millis()), false),
+                },
+                None => (executor.execute(ctx).await, true),
+            };
+            let Some(policy) = &policy else {
+                if finished {
+                    *variables = attempt_vars;
+                }
+                return result;
+            };
+
+            let number = attempts.len() as u32 + 1;
+            let delay = policy.next_delay(number, &result);
+            attempts.push(Attempt {
+                attempt: number,
+                status: result.status,
+                http_status: retry::http_status(&result),
+                error: result.error.clone(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                retry_in_ms: delay.map(|d| d.as_millis() as u64),
+            });
+
+            match delay {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => {
+                    if finished {
+                        *variables = attempt_vars;
                     }
+                    result.attempt_count = number;
+                    result.attempts = attempts;
+                    return result;
                 }
             }
         }
+    }
+}
 
-        // 2. Get Config
-        let condition = node.data.get("condition").and_then(|v| v.as_str()).unwrap_or("equals");
-        let query_value = node.data.get("value").and_then(|v| v.as_str()).unwrap_or("");
-        let message = node.data.get("message").and_then(|v| v.as_str()).unwrap_or("Assertion failed");
+/// Which of two ready nodes is scheduled first: the lower `data.order`
+/// (nodes that set one go before nodes that don't), then the one higher up on
+/// the canvas, then the one further left, then the lower id.
+fn schedule_tie_break(a: &Node, b: &Node) -> Ordering {
+    let order = |n: &Node| n.data.get("order").and_then(|v| v.as_f64());
+    match (order(a), order(b)) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+    .then(a.position.y.total_cmp(&b.position.y))
+    .then(a.position.x.total_cmp(&b.position.x))
+    .then_with(|| a.id.cmp(&b.id))
+}
 
-        let input_str = if input_val.is_string() {
-            input_val.as_str().unwrap().to_string()
-        } else {
-            input_val.to_string()
-        };
+/// Sleeps until `deadline`, or forever when there is none.
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
 
-        // 3. Evaluate
-        let passed = match condition {
-            "equals" => input_str == query_value,
-            "notEquals" => input_str != query_value,
-            "contains" => input_str.contains(query_value),
-            "notContains" => !input_str.contains(query_value),
-            "greaterThan" => {
-                 if let (Ok(curr), Ok(target)) = (input_str.parse::<f64>(), query_value.parse::<f64>()) {
-                     curr > target
-                 } else {
-                     false
-                 }
-            },
-            "lessThan" => {
-                 if let (Ok(curr), Ok(target)) = (input_str.parse::<f64>(), query_value.parse::<f64>()) {
-                     curr < target
-                 } else {
-                     false
-                 }
-            },
-             "regex" => {
-                if let Ok(re) = regex::Regex::new(query_value) {
-                    re.is_match(&input_str)
-                } else {
-                    false
-                }
-            },
-            _ => false,
-        };
+fn epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or_default()
+}
 
-        // 4. Return Result
-        if passed {
-            ExecutionResult {
-                node_id: node.id.clone(),
-                status: "success".to_string(),
-                output: serde_json::json!({ "status": "passed", "data": input_val }),
-                error: None,
-                active_handle: None,
-            }
-        } else {
-             ExecutionResult {
-                node_id: node.id.clone(),
-                status: "error".to_string(),
-                output: serde_json::json!({ "status": "failed", "actual": input_val, "expected": query_value }),
-                error: Some(format!("{}: Expected {} '{}', got '{}'", message, condition, query_value, input_str)),
-                active_handle: None,
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    fn flow(value: serde_json::Value) -> Flow {
+        Flow::from_json(&value.to_string()).unwrap()
     }
 
+    async fn run(engine: &WorkflowEngine, flow: &Flow) -> FlowRun {
+        engine.execute(flow, HashMap::new(), &CancellationToken::new()).await.unwrap()
+    }
 
-    // Helper methods for Variable Ops
-    fn get_first_parent_data(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>) -> serde_json::Value {
-        if let Some(parents) = rev_adj.get(&node.id) {
-            if let Some(first_parent) = parents.first() {
-                if let Some(parent_res) = prior_results.get(first_parent) {
-                    return parent_res.output.get("data").cloned().unwrap_or(parent_res.output.clone());
-                }
-            }
+    /// Counts how many of its nodes are running at once.
+    #[derive(Clone, Default)]
+    struct Probe {
+        running: Arc<AtomicUsize>,
+        peak: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeExecutor for Probe {
+        async fn execute(&self, ctx: NodeContext<'_>) -> ExecutionResult {
+            let now = self.running.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+            self.peak.fetch_max(now, AtomicOrdering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.running.fetch_sub(1, AtomicOrdering::SeqCst);
+            ExecutionResult::success(ctx.node.id.clone(), serde_json::json!({}))
         }
-        serde_json::Value::Null
     }
 
-    fn get_variable_as_array(&self, variables: &HashMap<String, serde_json::Value>, variable_name: &str) -> Vec<serde_json::Value> {
-        if let Some(curr) = variables.get(variable_name) {
-            if let Some(a) = curr.as_array() {
-                a.clone()
-            } else if curr.is_null() {
-                Vec::new()
-            } else {
-                vec![curr.clone()]
-            }
-        } else {
-            Vec::new()
+    fn fan_out(count: usize) -> Flow {
+        let mut nodes = vec![serde_json::json!({ "id": "s", "type": "start", "data": {} })];
+        let mut edges = Vec::new();
+        for i in 0..count {
+            nodes.push(serde_json::json!({ "id": format!("p{}", i), "type": "probe", "data": {} }));
+            edges.push(serde_json::json!({ "id": format!("e{}", i), "source": "s", "target": format!("p{}", i) }));
         }
+        flow(serde_json::json!({ "nodes": nodes, "edges": edges }))
     }
 
-    // Server Trigger Node
-    fn execute_server_trigger_node(&self, node: &Node, variables: &HashMap<String, serde_json::Value>) -> ExecutionResult {
-        // If we are in 'serve' mode, these variables should be populated
-        let method = variables.get("req_method").cloned().unwrap_or(serde_json::Value::Null);
-        let body = variables.get("req_body").cloned().unwrap_or(serde_json::Value::Null);
-        let query = variables.get("req_query").cloned().unwrap_or(serde_json::Value::Null);
-
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "success".to_string(),
-            output: serde_json::json!({
-                "method": method,
-                "body": body,
-                "query": query,
-                // "data" field for compatibility with generic nodes that expect "data"
-                "data": body 
-            }),
-            error: None,
-            active_handle: None,
+    #[tokio::test]
+    async fn batches_are_limited_by_max_concurrency() {
+        for (limit, expected) in [(1, 1), (2, 2), (8, 5)] {
+            let probe = Probe::default();
+            let mut engine = WorkflowEngine::new().with_max_concurrency(limit);
+            engine.register("probe", probe.clone());
+            let run = run(&engine, &fan_out(5)).await;
+            assert_eq!(run.trace.len(), 6);
+            assert_eq!(probe.peak.load(AtomicOrdering::SeqCst), expected, "max_concurrency {}", limit);
         }
     }
 
-    // Server Response Node
-    fn execute_server_response_node(&self, node: &Node, prior_results: &HashMap<String, ExecutionResult>, rev_adj: &HashMap<String, Vec<String>>, variables: &HashMap<String, serde_json::Value>) -> ExecutionResult {
-        let input_data = self.get_first_parent_data(node, prior_results, rev_adj);
-        
-        let status = node.data.get("status").and_then(|v| v.as_i64()).unwrap_or(200);
-        let body = node.data.get("body").cloned().unwrap_or(serde_json::Value::Null);
-        
-        let processed_body = if let Some(body_str) = body.as_str() {
-            serde_json::Value::String(self.substitute(body_str, variables))
-        } else {
-            body
+    #[tokio::test]
+    async fn concurrency_does_not_change_variable_writes() {
+        let flow = flow(serde_json::json!({
+            "nodes": [
+                { "id": "s", "type": "start", "data": {} },
+                { "id": "a", "type": "input", "data": { "value": "A" } },
+                { "id": "b", "type": "input", "data": { "value": "B" } },
+                { "id": "n1", "type": "counter", "data": { "variable": "n", "amount": 1 } },
+                { "id": "n2", "type": "counter", "data": { "variable": "n", "amount": 1 } },
+                { "id": "l1", "type": "counter", "data": { "variable": "list", "operation": "append" } },
+                { "id": "l2", "type": "counter", "data": { "variable": "list", "operation": "append" } },
+                { "id": "c", "type": "capture", "data": { "variable": "seen", "path": "$" } }
+            ],
+            "edges": [
+                { "id": "e1", "source": "s", "target": "a" },
+                { "id": "e2", "source": "s", "target": "b" },
+                { "id": "e3", "source": "s", "target": "n1" },
+                { "id": "e4", "source": "s", "target": "n2" },
+                { "id": "e5", "source": "a", "target": "l1" },
+                { "id": "e6", "source": "b", "target": "l2" },
+                { "id": "e7", "source": "a", "target": "c" }
+            ]
+        }));
+
+        let serial = run(&WorkflowEngine::new().with_max_concurrency(1), &flow).await;
+        let concurrent = run(&WorkflowEngine::new().with_max_concurrency(8), &flow).await;
+
+        assert_eq!(serial.variables, concurrent.variables);
+        assert_eq!(concurrent.variables["n"], serde_json::json!(2.0));
+        assert_eq!(concurrent.variables["list"].as_array().map(|a| a.len()), Some(2));
+        let order = |run: &FlowRun| run.trace.iter().map(|r| r.node_id.clone()).collect::<Vec<_>>();
+        assert_eq!(order(&serial), order(&concurrent));
+    }
+
+    fn join_flow(mode: &str) -> Flow {
+        flow(serde_json::json!({
+            "nodes": [
+                { "id": "s", "type": "start", "data": {} },
+                { "id": "a", "type": "input", "data": { "value": "A" } },
+                { "id": "b", "type": "input", "data": { "value": "B" } },
+                { "id": "b2", "type": "mapper", "data": { "mapping": { "B": "BB" } } },
+                { "id": "j", "type": "counter", "data": { "join": mode, "variable": "seen", "operation": "append" } }
+            ],
+            "edges": [
+                { "id": "e1", "source": "s", "target": "a" },
+                { "id": "e2", "source": "s", "target": "b" },
+                { "id": "e3", "source": "b", "target": "b2" },
+                { "id": "e4", "source": "a", "target": "j" },
+                { "id": "e5", "source": "b2", "target": "j" }
+            ]
+        }))
+    }
+
+    #[tokio::test]
+    async fn join_modes_gate_a_node_with_several_parents() {
+        let engine = WorkflowEngine::new();
+
+        let each = run(&engine, &join_flow("each")).await;
+        assert_eq!(each.variables["seen"], serde_json::json!(["A", "BB"]));
+
+        // "all" waits for both parents and runs once, with the first input as its primary one.
+        let all = run(&engine, &join_flow("all")).await;
+        assert_eq!(all.trace.iter().filter(|r| r.node_id == "j").count(), 1);
+        assert_eq!(all.variables["seen"], serde_json::json!(["A"]));
+        assert_eq!(all.trace.last().map(|r| r.node_id.as_str()), Some("j"));
+
+        let first = run(&engine, &join_flow("first")).await;
+        assert_eq!(first.variables["seen"], serde_json::json!(["A"]));
+        assert_eq!(first.trace.iter().filter(|r| r.node_id == "j").count(), 1);
+    }
+
+    #[tokio::test]
+    async fn checkpoints_leave_out_secrets_and_never_fail_the_run() {
+        let mut flow = join_flow("each");
+        flow.parameters = serde_json::from_value(serde_json::json!([
+            { "name": "token", "secret": true, "default": "s3cret" },
+            { "name": "region", "default": "eu" }
+        ])).unwrap();
+
+        let dir = std::env::temp_dir().join(format!("knotwork-engine-checkpoint-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("run.json");
+        let finished = run(&WorkflowEngine::new().with_checkpoints(&path), &flow).await;
+        let saved = Checkpoint::load(&path).unwrap();
+        assert_eq!(saved.trace.len(), finished.trace.len());
+        assert_eq!(saved.variables.get("region"), Some(&serde_json::json!("eu")));
+        assert!(!saved.variables.contains_key("token"));
+        assert!(!fs::read_to_string(&path).unwrap().contains("s3cret"));
+        let _ = fs::remove_dir_all(&dir);
+
+        // A checkpoint "directory" that is really a file can't be written to.
+        let blocked = std::env::temp_dir().join(format!("knotwork-engine-blocked-{}", std::process::id()));
+        fs::write(&blocked, "").unwrap();
+        let unsaved = run(&WorkflowEngine::new().with_checkpoints(blocked.join("run.json")), &flow).await;
+        assert_eq!(unsaved.variables["seen"], serde_json::json!(["A", "BB"]));
+        let _ = fs::remove_file(&blocked);
+    }
+
+    #[tokio::test]
+    async fn visit_cap_leaves_the_rest_of_the_batch_queued() {
+        let flow = |max_visits: usize| Flow::from_json(&serde_json::json!({
+            "settings": { "maxVisits": max_visits },
+            "nodes": [
+                { "id": "s", "type": "start", "data": {} },
+                { "id": "a", "type": "input", "data": { "value": "a" } },
+                { "id": "b", "type": "input", "data": { "value": "b" } }
+            ],
+            "edges": [{ "id": "sa", "source": "s", "target": "a" }, { "id": "sb", "source": "s", "target": "b" }]
+        }).to_string()).unwrap();
+        let dir = std::env::temp_dir().join(format!("knotwork-engine-visits-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("run.json");
+
+        // `a` already used up its visits, so the batch holding `a` and `b` never starts.
+        let checkpoint = Checkpoint {
+            current: vec![QueuedNode { node_id: "s".to_string(), arrived: Vec::new() }],
+            visits: HashMap::from([("a".to_string(), 1)]),
+            ..Default::default()
         };
+        let engine = WorkflowEngine::new().with_checkpoints(&path);
+        let stopped = engine.resume(&flow(1), checkpoint, &CancellationToken::new()).await.unwrap();
+        assert!(stopped.results["a"].is_error());
+        assert!(!stopped.results.contains_key("b"));
+
+        let saved = Checkpoint::load(&path).unwrap();
+        assert_eq!(saved.visits.get("b"), None);
+        let resumed = engine.resume(&flow(5), saved, &CancellationToken::new()).await.unwrap();
+        assert_eq!(resumed.results["b"].output["data"], "b");
+        let _ = fs::remove_dir_all(&dir);
+    }
 
-        ExecutionResult {
-            node_id: node.id.clone(),
-            status: "completed".to_string(),
-            output: serde_json::json!({
-                "server_response": {
-                    "status": status,
-                    "body": processed_body,
-                    "source_data": input_data
-                }
-            }),
-            error: None,
-            active_handle: None,
+    /// Hangs on its first run and succeeds on every later one.
+    #[derive(Clone, Default)]
+    struct HangsOnce {
+        calls: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl NodeExecutor for HangsOnce {
+        async fn execute(&self, ctx: NodeContext<'_>) -> ExecutionResult {
+            if self.calls.fetch_add(1, AtomicOrdering::SeqCst) == 0 {
+                std::future::pending::<()>().await;
+            }
+            ExecutionResult::success(ctx.node.id.clone(), serde_json::json!({}))
         }
     }
+
+    #[tokio::test]
+    async fn node_timeout_applies_to_each_retry_attempt() {
+        let mut engine = WorkflowEngine::new();
+        engine.register("hangs", HangsOnce::default());
+        let flow = flow(serde_json::json!({
+            "nodes": [{ "id": "h", "type": "hangs", "data": { "timeoutMs": 50, "retry": { "maxAttempts": 2, "delayMs": 0, "jitter": false } } }],
+            "edges": []
+        }));
+
+        let result = &run(&engine, &flow).await.results["h"];
+        assert_eq!(result.status, ExecutionStatus::Success);
+        assert_eq!(result.attempt_count, 2);
+        assert_eq!(result.attempts[0].error.as_deref(), Some("Node timed out after 50ms"));
+    }
+
+    #[tokio::test]
+    async fn incomplete_all_join_is_reported_as_skipped() {
+        // A condition only fires its "true" or "false" edge, so `b2` never runs.
+        let mut flow = join_flow("all");
+        let b = flow.nodes.iter_mut().find(|n| n.id == "b").unwrap();
+        b.node_type = "condition".into();
+        b.data = serde_json::json!({ "condition": "equal", "targetValue": "x" });
+        let run = run(&WorkflowEngine::new(), &flow).await;
+        assert_eq!(run.results["j"].status, ExecutionStatus::Skipped);
+        assert_eq!(run.results["j"].output["waiting_for"], serde_json::json!(["b2"]));
+    }
 }
@@ -13,6 +13,7 @@ import ReactFlow, {
 } from "reactflow";
 import { invoke } from "@tauri-apps/api/core";
 import { FilesSidebar } from "./files-sidebar";
+import { FlowMeta } from "../types";
 
 import {
   ContextMenu,
@@ -47,6 +48,8 @@ import CommentNode from "./nodes/CommentNode";
 import GroupNode from "./nodes/GroupNode";
 import ServerTriggerNode from "./nodes/ServerTriggerNode";
 import ServerResponseNode from "./nodes/ServerResponseNode";
+import SubflowNode from "./nodes/SubflowNode";
+import ExpressionNode from "./nodes/ExpressionNode";
 
 const nodeTypes: NodeTypes = {
   httpRequest: HttpRequestNode,
@@ -69,6 +72,8 @@ const nodeTypes: NodeTypes = {
   group: GroupNode,
   serverTrigger: ServerTriggerNode,
   serverResponse: ServerResponseNode,
+  subflow: SubflowNode,
+  expression: ExpressionNode,
 };
 
 const initialNodes: Node[] = [];
@@ -77,13 +82,16 @@ export default function ApiFlow() {
   const [nodes, setNodes, onNodesChange] = useNodesState(initialNodes);
   const [edges, setEdges, onEdgesChange] = useEdgesState([]);
   const [variables] = useState<Record<string, any>>({});
+  const [flowMeta, setFlowMeta] = useState<FlowMeta>({});
 
   const onLoadFlow = useCallback(async (path: string) => {
     try {
       const flow = await invoke<any>("load_flow", { path });
       if (flow && flow.nodes && flow.edges) {
-        setNodes(flow.nodes);
-        setEdges(flow.edges);
+        const { nodes, edges, ...meta } = flow;
+        setNodes(nodes);
+        setEdges(edges);
+        setFlowMeta(meta);
       }
     } catch (e) {
       console.error("Failed to load flow:", e);
@@ -115,6 +123,7 @@ export default function ApiFlow() {
     if (window.confirm("Are you sure you want to clear the entire flow?")) {
       setNodes([]);
       setEdges([]);
+      setFlowMeta({});
     }
   }, [setNodes, setEdges]);
 
@@ -139,7 +148,7 @@ export default function ApiFlow() {
                 >
                   <Background color="#999" gap={16} />
                   <Panel position="bottom-center" className="pb-4">
-                    <FlowControlsDock />
+                    <FlowControlsDock flowMeta={flowMeta} onFlowMetaChange={setFlowMeta} />
                   </Panel>
                 </ReactFlow>
               </div>
@@ -160,8 +169,10 @@ export default function ApiFlow() {
                 <ContextMenuSubTrigger inset>Logic</ContextMenuSubTrigger>
                 <ContextMenuSubContent className="w-48">
                   <ContextMenuItem inset onClick={() => addNode('condition')}>Conditional</ContextMenuItem>
+                  <ContextMenuItem inset onClick={() => addNode('expression', { expression: "" })}>Expression</ContextMenuItem>
                   <ContextMenuItem inset onClick={() => addNode('assert')}>Assert (Test)</ContextMenuItem>
                   <ContextMenuItem inset onClick={() => addNode('loop')}>Loop (ForEach)</ContextMenuItem>
+                  <ContextMenuItem inset onClick={() => addNode('subflow', { path: "", inputs: {} })}>Subflow</ContextMenuItem>
                 </ContextMenuSubContent>
               </ContextMenuSub>
 
@@ -1,41 +1,146 @@
-import { IconBox, IconLock, IconLockOpen, IconRefresh, IconZoomIn, IconZoomOut, IconDeviceFloppy, IconFolderOpen, IconSettings } from "@tabler/icons-react";
+import { IconBox, IconBug, IconCircleDot, IconLock, IconLockOpen, IconPlayerPlay, IconPlayerSkipForward, IconPlayerStop, IconPlayerTrackNext, IconRefresh, IconVariable, IconZoomIn, IconZoomOut, IconDeviceFloppy, IconFolderOpen, IconSettings } from "@tabler/icons-react";
 import { useReactFlow } from "reactflow";
 import { FloatingDock } from "./ui/floating-dock";
-import { useState } from "react";
+import { useRef, useState } from "react";
 import { Moon, Play, Sun } from "lucide-react";
 import useThemes from "../hooks/useThemes";
 import { invoke } from "@tauri-apps/api/core";
-import { ExecutionResult } from "../types";
+import { listen } from "@tauri-apps/api/event";
+import { FlowEvent, FlowMeta, FlowRun, PausedState } from "../types";
 import { toast } from "sonner";
 import { EnvironmentManager } from "./EnvironmentManager";
+import { RunParametersDialog } from "./RunParametersDialog";
 
-export const FlowControlsDock = ({ className, onExecutionComplete }: { className?: string, onExecutionComplete?: (vars: Record<string, any>) => void }) => {
+// Runs only part of the flow: from a node onwards, or just what a node depends on
+type RunScope = { from: string } | { until: string };
+
+export const FlowControlsDock = ({ className, onExecutionComplete, flowMeta = {}, onFlowMetaChange }: { className?: string, onExecutionComplete?: (vars: Record<string, any>) => void, flowMeta?: FlowMeta, onFlowMetaChange?: (meta: FlowMeta) => void }) => {
   const { setNodes, getNodes, getEdges, zoomIn, zoomOut, fitView, setEdges } = useReactFlow();
   const [locked, setLocked] = useState(false);
   const [isEnvModalOpen, setEnvModalOpen] = useState(false);
   const { theme, toggleTheme } = useThemes()
   const [isRunning, setIsRunning] = useState(false);
+  const runIdRef = useRef<string | null>(null);
+  // Identifies the flow on the canvas, so "run from here" only reuses its own last run
+  const flowKeyRef = useRef<string>(crypto.randomUUID());
+  const [pausedAt, setPausedAt] = useState<string | null>(null);
+  // A flow that declares parameters asks for them before it runs
+  const [pendingRun, setPendingRun] = useState<{ debug: boolean, scope?: RunScope, env: Record<string, string> } | null>(null);
+
+  const startRun = async (debug = false, scope?: RunScope) => {
+    if (!flowMeta.parameters?.length) return runFlow(debug, undefined, scope);
+    const env = await invoke<Record<string, string>>("load_environment").catch(() => ({}));
+    setPendingRun({ debug, scope, env });
+  };
+
+  const cancelFlow = async () => {
+    if (!runIdRef.current) return;
+    await invoke<boolean>("cancel_flow", { runId: runIdRef.current });
+  };
+
+  const stepFlow = async () => {
+    if (!runIdRef.current) return;
+    await invoke<boolean>("debug_step", { runId: runIdRef.current });
+  };
+
+  const continueFlow = async () => {
+    if (!runIdRef.current) return;
+    await invoke<boolean>("debug_continue", { runId: runIdRef.current });
+  };
+
+  const inspectPaused = async () => {
+    if (!runIdRef.current) return;
+    const state = await invoke<PausedState | null>("debug_state", { runId: runIdRef.current });
+    if (!state) return;
+    console.log("Paused before", state.node_id, state);
+    alert(`Paused before ${state.node_id}\n\nVariables:\n${JSON.stringify(state.variables, null, 2)}`);
+  };
+
+  const editVariable = async () => {
+    if (!runIdRef.current) return;
+    const name = window.prompt("Variable name:");
+    if (!name) return;
+    const raw = window.prompt(`New value for ${name} (JSON):`);
+    if (raw === null) return;
+    let value: any = raw;
+    try { value = JSON.parse(raw); } catch { /* keep as plain string */ }
+    try {
+      await invoke("debug_set_variable", { runId: runIdRef.current, name, value });
+    } catch (error) {
+      toast.error(`Could not set ${name}: ${error}`);
+    }
+  };
+
+  const toggleBreakpoints = () => {
+    setNodes((nds) => nds.map((n) => n.selected ? { ...n, data: { ...n.data, breakpoint: !n.data.breakpoint } } : n));
+  };
+
+  const selectedNodeId = () => {
+    const selected = getNodes().filter((n) => n.selected);
+    if (selected.length !== 1) {
+      toast.warning("Select a single node first");
+      return null;
+    }
+    return selected[0].id;
+  };
+
+  const runFromSelected = () => {
+    const nodeId = selectedNodeId();
+    if (nodeId) runFlow(false, undefined, { from: nodeId });
+  };
 
-  const runFlow = async () => {
+  const runUntilSelected = () => {
+    const nodeId = selectedNodeId();
+    if (nodeId) startRun(false, { until: nodeId });
+  };
+
+  const runFlow = async (debug = false, resumeFrom?: string, scope?: RunScope, params: Record<string, string> = {}) => {
     console.log("Run flow");
+    const runId = crypto.randomUUID();
+    runIdRef.current = runId;
+    const flowKey = flowKeyRef.current;
     setIsRunning(true);
-    const nodes = getNodes();
-    const edges = getEdges();
+    const flow = { nodes: getNodes(), edges: getEdges(), ...flowMeta };
+    const edges = flow.edges;
+
+    // Reflect progress on the canvas while the backend is still running
+    setEdges((eds) => eds.map((edge) => ({ ...edge, animated: false })));
+    const unlisten = await listen<FlowEvent>("flow-event", ({ payload }) => {
+      if (payload.type === "paused") {
+        setPausedAt(payload.node_id);
+        toast.info(`Paused before ${payload.node_id}`);
+      } else if (payload.type === "node_started") {
+        setPausedAt(null);
+        setNodes((nds) => nds.map((n) => n.id === payload.node_id ? { ...n, data: { ...n.data, running: true } } : n));
+      } else if (payload.type === "node_finished") {
+        const result = payload.result;
+        setNodes((nds) => nds.map((n) => n.id === result.node_id ? { ...n, data: { ...n.data, running: false, executionResult: result } } : n));
+      } else if (payload.type === "edge_fired") {
+        setEdges((eds) => eds.map((edge) => edge.id === payload.edge_id ? { ...edge, animated: true } : edge));
+      }
+    });
 
     try {
-      const env = await invoke<Record<string, string>>("load_environment").catch(() => ({}));
+      const env = { ...await invoke<Record<string, string>>("load_environment").catch(() => ({})), ...params };
 
-      const [results, variables] = await invoke<[Record<string, ExecutionResult>, Record<string, any>]>("execute_flow", {
-        flow: { nodes, edges },
-        env
-      });
+      // Resuming picks up the earlier run's checkpoint instead of starting over
+      // Running from a node reuses the upstream results of the last run
+      const { results, variables, trace } = resumeFrom
+        ? await invoke<FlowRun>("resume_flow", { flow, fromRunId: resumeFrom, env, runId, flowKey, debug })
+        : scope && "from" in scope
+          ? await invoke<FlowRun>("execute_from_node", { flow, nodeId: scope.from, runId, flowKey, debug })
+          : scope && "until" in scope
+            ? await invoke<FlowRun>("execute_until_node", { flow, nodeId: scope.until, env, runId, flowKey, debug })
+            : await invoke<FlowRun>("execute_flow", { flow, env, runId, flowKey, debug });
 
       console.log("Execution results:", results);
       if (onExecutionComplete) onExecutionComplete(variables);
 
       const errors = Object.values(results).filter(r => r.status === 'error');
-      if (errors.length > 0) {
-        toast.error(`Flow failed: ${errors[0].error || 'Unknown error'}`);
+      if (Object.values(results).some(r => r.status === 'cancelled')) {
+        toast.warning("Flow cancelled", { action: { label: "Resume", onClick: () => runFlow(false, runId, undefined, params) } });
+      } else if (errors.length > 0) {
+        toast.error(`Flow failed: ${errors[0].error || 'Unknown error'}`, { action: { label: "Resume", onClick: () => runFlow(false, runId, undefined, params) } });
       } else {
         toast.success("Flow executed successfully!");
       }
@@ -48,6 +153,7 @@ export const FlowControlsDock = ({ className, onExecutionComplete }: { className
             data: {
               ...node.data,
               executionResult: result,
+              executionHistory: trace.filter(r => r.node_id === node.id),
             }
           };
         }
@@ -92,6 +198,9 @@ export const FlowControlsDock = ({ className, onExecutionComplete }: { className
       console.error("Flow execution failed:", error);
       alert("Flow execution failed: " + error);
     } finally {
+      unlisten();
+      runIdRef.current = null;
+      setPausedAt(null);
       setIsRunning(false);
     }
   };
@@ -104,7 +213,7 @@ export const FlowControlsDock = ({ className, onExecutionComplete }: { className
     const edges = getEdges();
 
     try {
-      await invoke("save_flow", { path, flow: { nodes, edges } });
+      await invoke("save_flow", { path, flow: { nodes, edges, ...flowMeta } });
       alert("Flow saved successfully!");
     } catch (error) {
       console.error("Save failed:", error);
@@ -117,9 +226,11 @@ export const FlowControlsDock = ({ className, onExecutionComplete }: { className
     if (!path) return;
 
     try {
-      const flow: any = await invoke("load_flow", { path });
-      setNodes(flow.nodes || []);
-      setEdges(flow.edges || []);
+      const { nodes, edges, ...meta }: any = await invoke("load_flow", { path });
+      setNodes(nodes || []);
+      setEdges(edges || []);
+      onFlowMetaChange?.(meta);
+      flowKeyRef.current = crypto.randomUUID();
       alert("Flow loaded successfully!");
     } catch (error) {
       console.error("Load failed:", error);
@@ -140,7 +251,18 @@ export const FlowControlsDock = ({ className, onExecutionComplete }: { className
     { title: "Zoom In", icon: <IconZoomIn />, action: () => zoomIn() },
     { title: "Zoom Out", icon: <IconZoomOut />, action: () => zoomOut() },
     { title: "Reset", icon: <IconRefresh />, action: () => fitView({ duration: 1000 }) },
-    { title: "Run flow", icon: isRunning ? <IconRefresh className="h-6 w-6 animate-spin" /> : <Play className="h-6 w-6" />, action: () => runFlow() },
+    { title: isRunning ? "Stop flow" : "Run flow", icon: isRunning ? <IconRefresh className="h-6 w-6 animate-spin" /> : <Play className="h-6 w-6" />, action: () => isRunning ? cancelFlow() : startRun() },
+    ...(pausedAt ? [
+      { title: "Step", icon: <IconPlayerTrackNext />, action: stepFlow },
+      { title: "Continue", icon: <IconPlayerSkipForward />, action: continueFlow },
+      { title: "Inspect", icon: <IconBug />, action: inspectPaused },
+      { title: "Edit Variable", icon: <IconVariable />, action: editVariable },
+    ] : isRunning ? [] : [
+      { title: "Debug flow", icon: <IconBug />, action: () => startRun(true) },
+      { title: "Run from here", icon: <IconPlayerPlay />, action: runFromSelected },
+      { title: "Run up to here", icon: <IconPlayerStop />, action: runUntilSelected },
+    ]),
+    { title: "Toggle Breakpoint", icon: <IconCircleDot />, action: toggleBreakpoints },
     { title: "Save Flow", icon: <IconDeviceFloppy />, action: saveFlow },
     { title: "Load Flow", icon: <IconFolderOpen />, action: loadFlow },
     { title: "Environment", icon: <IconSettings />, action: () => setEnvModalOpen(true) },
@@ -156,6 +278,14 @@ export const FlowControlsDock = ({ className, onExecutionComplete }: { className
   return (
     <>
       <EnvironmentManager open={isEnvModalOpen} onOpenChange={setEnvModalOpen} />
+      <RunParametersDialog
+        open={pendingRun !== null}
+        onOpenChange={(open) => !open && setPendingRun(null)}
+        parameters={flowMeta.parameters || []}
+        outputs={flowMeta.outputs}
+        env={pendingRun?.env || {}}
+        onRun={(params) => pendingRun && runFlow(pendingRun.debug, undefined, pendingRun.scope, params)}
+      />
       <FloatingDock items={items} desktopClassName={className} mobileClassName="translate-y-20" />
     </>
   );
@@ -0,0 +1,75 @@
+import { useState, useEffect } from "react";
+import { Button } from "./ui/button";
+import {
+    Dialog,
+    DialogContent,
+    DialogDescription,
+    DialogHeader,
+    DialogTitle,
+} from "./ui/dialog";
+import { Input } from "./ui/input";
+import { Label } from "./ui/label";
+import { FlowOutput, FlowParameter } from "../types";
+
+const toText = (value: any) => value === undefined || value === null ? "" : typeof value === "string" ? value : JSON.stringify(value);
+
+export function RunParametersDialog({ open, onOpenChange, parameters, outputs, env, onRun }: {
+    open: boolean,
+    onOpenChange: (open: boolean) => void,
+    parameters: FlowParameter[],
+    outputs?: FlowOutput[],
+    env: Record<string, string>,
+    onRun: (values: Record<string, string>) => void,
+}) {
+    const [values, setValues] = useState<Record<string, string>>({});
+
+    // Start from the environment, then the declared defaults
+    useEffect(() => {
+        if (open) {
+            setValues(Object.fromEntries(parameters.map((p) => [p.name, env[p.name] ?? toText(p.default)])));
+        }
+    }, [open, parameters, env]);
+
+    const missing = parameters.filter((p) => p.required && !values[p.name]);
+
+    const handleRun = () => {
+        // Empty optional fields are left out so the flow sees them as unset
+        onRun(Object.fromEntries(Object.entries(values).filter(([, v]) => v !== "")));
+        onOpenChange(false);
+    };
+
+    return (
+        <Dialog open={open} onOpenChange={onOpenChange}>
+            <DialogContent className="sm:max-w-[425px] bg-white dark:bg-gray-900 border-gray-700 text-foreground">
+                <DialogHeader>
+                    <DialogTitle>Run Flow</DialogTitle>
+                    <DialogDescription>
+                        This flow takes the parameters below.
+                        {outputs && outputs.length > 0 && <> It produces {outputs.map((o) => <code key={o.name} className="bg-muted px-1 mx-0.5 rounded">{o.name}</code>)}.</>}
+                    </DialogDescription>
+                </DialogHeader>
+                <div className="py-4 space-y-3">
+                    {parameters.map((param) => (
+                        <div key={param.name} className="grid gap-1">
+                            <Label className="text-xs">
+                                {param.name}
+                                {param.required && <span className="text-red-500"> *</span>}
+                                <span className="text-muted-foreground font-normal"> ({param.type || "string"})</span>
+                            </Label>
+                            <Input
+                                type={param.secret ? "password" : "text"}
+                                value={values[param.name] ?? ""}
+                                onChange={(e) => setValues({ ...values, [param.name]: e.target.value })}
+                                className="h-8 text-xs font-mono"
+                            />
+                            {param.description && <p className="text-[10px] text-muted-foreground">{param.description}</p>}
+                        </div>
+                    ))}
+                </div>
+                <div className="flex justify-end">
+                    <Button onClick={handleRun} disabled={missing.length > 0}>Run</Button>
+                </div>
+            </DialogContent>
+        </Dialog>
+    );
+}
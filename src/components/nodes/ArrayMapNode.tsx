@@ -5,6 +5,7 @@ import { Input } from "../ui/input";
 import { Button } from "../ui/button";
 import { Card } from "../ui/card";
 import { Badge } from "../ui/badge";
+import ErrorHandle from "./ErrorHandle";
 
 export interface ArrayMapNodeData {
     path: string;
@@ -51,6 +52,7 @@ export default function ArrayMapNode({ data, id }: { data: ArrayMapNodeData; id:
 
                 <Handle type="target" position={Position.Top} className="w-2 h-2" />
                 <Handle type="source" position={Position.Bottom} className="w-2 h-2" />
+                <ErrorHandle />
             </Card>
         );
     }
@@ -79,6 +81,7 @@ export default function ArrayMapNode({ data, id }: { data: ArrayMapNodeData; id:
                 </div>
             </div>
             <Handle type="source" position={Position.Bottom} className="w-2 h-2" />
+            <ErrorHandle />
         </Card>
     );
 }
@@ -2,26 +2,32 @@ import { Handle, Position } from "reactflow";
 import { Card, CardContent, CardHeader } from "../ui/card";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "../ui/select";
 import { Input } from "../ui/input";
+import { Textarea } from "../ui/textarea";
 import { Label } from "../ui/label";
 import { useState } from "react";
 import { AlertCircle } from "lucide-react";
+import ErrorHandle from "./ErrorHandle";
 
 export default function AssertNode({ id, data }: { id: string, data: any }) {
 
     const [condition, setCondition] = useState(data.condition || 'equals');
     const [value, setValue] = useState(data.value || '');
     const [message, setMessage] = useState(data.message || '');
+    const [expression, setExpression] = useState(data.expression || '');
 
     const updateData = (key: string, val: any) => {
         if (key === 'condition') setCondition(val);
         if (key === 'value') setValue(val);
         if (key === 'message') setMessage(val);
+        if (key === 'expression') setExpression(val);
         data.onSave?.(id, { ...data, [key]: val });
     }
 
     return (
         <Card className="min-w-[250px] bg-white border-2 text-foreground dark:bg-gray-950 dark:text-gray-300 border-orange-500 shadow-md ring-2 ring-orange-500/20">
             <Handle type="target" position={Position.Left} className="w-3 h-3 bg-orange-500" />
+            <Handle type="target" position={Position.Top} id="actual" className="w-3 h-3 bg-blue-500" style={{ left: "35%" }} title="actual" />
+            <Handle type="target" position={Position.Top} id="expected" className="w-3 h-3 bg-green-500" style={{ left: "65%" }} title="expected" />
 
             <CardHeader className="p-3 pb-2 bg-orange-50 dark:bg-orange-900/20 font-bold text-sm text-orange-700 dark:text-orange-400 flex flex-row items-center gap-2">
                 <AlertCircle className="w-4 h-4" />
@@ -43,19 +49,32 @@ export default function AssertNode({ id, data }: { id: string, data: any }) {
                             <SelectItem value="greaterThan">Greater Than (&gt;)</SelectItem>
                             <SelectItem value="lessThan">Less Than (&lt;)</SelectItem>
                             <SelectItem value="regex">Regex Match</SelectItem>
+                            <SelectItem value="expression">Expression</SelectItem>
                         </SelectContent>
                     </Select>
                 </div>
 
-                <div className="space-y-1">
-                    <Label className="text-xs">Expected Value</Label>
-                    <Input
-                        value={value}
-                        onChange={(e) => updateData('value', e.target.value)}
-                        className="h-8 text-xs font-mono"
-                        placeholder="Value to assert..."
-                    />
-                </div>
+                {condition === 'expression' ? (
+                    <div className="space-y-1">
+                        <Label className="text-xs">Expression</Label>
+                        <Textarea
+                            value={expression}
+                            onChange={(e) => updateData('expression', e.target.value)}
+                            className="font-mono text-[10px] h-16"
+                            placeholder="actual.len() > 0 && actual[0].id == expected"
+                        />
+                    </div>
+                ) : (
+                    <div className="space-y-1">
+                        <Label className="text-xs">Expected Value</Label>
+                        <Input
+                            value={value}
+                            onChange={(e) => updateData('value', e.target.value)}
+                            className="h-8 text-xs font-mono"
+                            placeholder="Value to assert..."
+                        />
+                    </div>
+                )}
 
                 <div className="space-y-1">
                     <Label className="text-xs">Failure Message (Optional)</Label>
@@ -73,6 +92,7 @@ export default function AssertNode({ id, data }: { id: string, data: any }) {
                 position={Position.Right}
                 className="w-3 h-3 bg-green-500"
             />
+            <ErrorHandle />
         </Card>
     );
 }
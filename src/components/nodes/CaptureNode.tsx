@@ -4,6 +4,7 @@ import { Card, CardContent, CardHeader, CardTitle } from "../ui/card";
 import { Input } from "../ui/input";
 import { Label } from "../ui/label";
 import { Regex } from "lucide-react";
+import ErrorHandle from "./ErrorHandle";
 
 export default memo(({ id: _id, data }: { id: string, data: any }) => {
     const [path, setPath] = useState(data.path || "");
@@ -48,6 +49,7 @@ export default memo(({ id: _id, data }: { id: string, data: any }) => {
 
             <Handle type="target" position={Position.Left} className="w-3 h-3 bg-yellow-500" />
             <Handle type="source" position={Position.Right} className="w-3 h-3 bg-yellow-500" />
+            <ErrorHandle />
         </Card>
     );
 });
@@ -5,6 +5,7 @@ import { motion, AnimatePresence } from "framer-motion";
 import { useState, useEffect } from "react";
 import { ChevronLeft, ChevronRight, Image as ImageIcon } from "lucide-react";
 import { Button } from "../ui/button";
+import ErrorHandle from "./ErrorHandle";
 
 export interface CarouselNodeData {
     items?: any[];
@@ -126,6 +127,7 @@ export default function CarouselNode({ data }: { data: CarouselNodeData }) {
             )}
 
             <Handle type="source" position={Position.Bottom} className="w-3 h-3 bg-indigo-500 opacity-20" />
+            <ErrorHandle />
         </Card>
     );
 }
@@ -2,18 +2,22 @@ import { Handle, Position } from "reactflow";
 import { Card, CardContent, CardHeader } from "../ui/card";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "../ui/select";
 import { Input } from "../ui/input";
+import { Textarea } from "../ui/textarea";
 import { Label } from "../ui/label";
 import { useState } from "react";
+import ErrorHandle from "./ErrorHandle";
 // import { NodeData } from "../../types";
 
 export default function ConditionalNode({ id, data }: { id: string, data: any }) {
 
   const [condition, setCondition] = useState(data.condition || 'equal');
   const [targetValue, setTargetValue] = useState(data.targetValue || '');
+  const [expression, setExpression] = useState(data.expression || '');
 
   const updateData = (key: string, value: any) => {
     if (key === 'condition') setCondition(value);
     if (key === 'targetValue') setTargetValue(value);
+    if (key === 'expression') setExpression(value);
     data.onSave?.(id, { ...data, [key]: value });
   }
 
@@ -38,20 +42,34 @@ export default function ConditionalNode({ id, data }: { id: string, data: any })
               <SelectItem value="greaterThan">Greater Than (&gt;)</SelectItem>
               <SelectItem value="lessThan">Less Than (&lt;)</SelectItem>
               <SelectItem value="contains">Contains (String)</SelectItem>
+              <SelectItem value="expression">Expression</SelectItem>
             </SelectContent>
           </Select>
         </div>
 
-        <div className="space-y-1">
-          <Label className="text-xs">Target Value</Label>
-          <Input
-            value={targetValue}
-            onChange={(e) => updateData('targetValue', e.target.value)}
-            className="h-8 text-xs"
-            placeholder="Value to compare..."
-          />
-          <p className="text-[10px] text-muted-foreground">Compares Input ⚡ vs Target</p>
-        </div>
+        {condition === 'expression' ? (
+          <div className="space-y-1">
+            <Label className="text-xs">Expression</Label>
+            <Textarea
+              value={expression}
+              onChange={(e) => updateData('expression', e.target.value)}
+              className="font-mono text-[10px] h-16"
+              placeholder="status == 200 && vars.retries < 3"
+            />
+            <p className="text-[10px] text-muted-foreground">Input fields by name, variables as vars.*</p>
+          </div>
+        ) : (
+          <div className="space-y-1">
+            <Label className="text-xs">Target Value</Label>
+            <Input
+              value={targetValue}
+              onChange={(e) => updateData('targetValue', e.target.value)}
+              className="h-8 text-xs"
+              placeholder="Value to compare..."
+            />
+            <p className="text-[10px] text-muted-foreground">Compares Input ⚡ vs Target</p>
+          </div>
+        )}
       </CardContent>
 
       {/* True Handle */}
@@ -76,6 +94,7 @@ export default function ConditionalNode({ id, data }: { id: string, data: any })
         <span className="text-[10px] text-red-600 font-bold mr-2 mb-[12px]">False</span>
       </div>
 
+      <ErrorHandle />
     </Card>
   );
 }
@@ -4,6 +4,7 @@ import { Label } from "../ui/label";
 import { Input } from "../ui/input";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "../ui/select";
 import { Calculator } from "lucide-react";
+import ErrorHandle from "./ErrorHandle";
 
 export default function CounterNode({ id, data }: { id: string, data: any }) {
     const updateData = (key: string, value: any) => {
@@ -59,6 +60,7 @@ export default function CounterNode({ id, data }: { id: string, data: any }) {
                 </div>
             </CardContent>
             <Handle type="source" position={Position.Right} className="w-3 h-3 bg-blue-500" />
+            <ErrorHandle />
         </Card>
     );
 }
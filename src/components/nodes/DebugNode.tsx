@@ -2,6 +2,7 @@ import { Handle, Position, useEdges, useNodes, } from "reactflow";
 import { Card, CardContent, CardHeader } from "../ui/card";
 import { Badge } from "../ui/badge";
 import { useCallback } from "react";
+import ErrorHandle from "./ErrorHandle";
 export default function DebugNode({ id, data }: { id: string, data: any }) {
     const edges = useEdges();
     const nodes = useNodes();
@@ -27,6 +28,7 @@ export default function DebugNode({ id, data }: { id: string, data: any }) {
             <CardContent>
                 {JSON.stringify(data)}
             </CardContent>
+            <ErrorHandle />
         </Card>
     )
 
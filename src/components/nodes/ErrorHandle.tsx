@@ -0,0 +1,15 @@
+import { Handle, Position } from "reactflow";
+
+/** Source handle the engine fires instead of the normal outputs when the node fails. */
+export default function ErrorHandle() {
+    return (
+        <Handle
+            id="error"
+            type="source"
+            position={Position.Bottom}
+            className="w-3 h-3 bg-red-600"
+            style={{ left: "90%" }}
+            title="error"
+        />
+    );
+}
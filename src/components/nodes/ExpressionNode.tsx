@@ -0,0 +1,60 @@
+import { Handle, Position } from "reactflow";
+import { Card, CardContent, CardHeader } from "../ui/card";
+import { Label } from "../ui/label";
+import { Textarea } from "../ui/textarea";
+import { useState } from "react";
+import ErrorHandle from "./ErrorHandle";
+
+export default function ExpressionNode({ id, data }: { id: string, data: any }) {
+  const [expression, setExpression] = useState(data.expression || '');
+
+  const onExpressionChange = (value: string) => {
+    setExpression(value);
+    data.onSave?.(id, { ...data, expression: value });
+  };
+
+  return (
+    <Card className="min-w-[250px] bg-white border text-foreground dark:bg-gray-900 dark:text-gray-300 border-purple-500 shadow-md">
+      <Handle type="target" position={Position.Left} className="w-3 h-3 bg-purple-500" />
+
+      <CardHeader className="p-3 pb-2 bg-purple-50 dark:bg-purple-900/20 font-bold text-sm text-purple-700 dark:text-purple-400">
+        ƒ Expression
+      </CardHeader>
+
+      <CardContent className="p-3 space-y-1">
+        <Label className="text-xs">Expression</Label>
+        <Textarea
+          value={expression}
+          onChange={(e) => onExpressionChange(e.target.value)}
+          className="font-mono text-[10px] h-16"
+          placeholder="status == 200 && data.items.len() > 0 && vars.retries < 3"
+        />
+        <p className="text-[10px] text-muted-foreground">Input fields by name, variables as vars.*, results as nodes.&lt;id&gt;</p>
+      </CardContent>
+
+      {/* True Handle */}
+      <div className="absolute right-[-6px] top-[30px] flex items-center flex-row-reverse">
+        <Handle
+          id="true"
+          type="source"
+          position={Position.Right}
+          className="w-3 h-3 bg-green-500 top-[37px]"
+        />
+        <span className="text-[10px] text-green-600 font-bold mr-2 mt-[6px]">True</span>
+      </div>
+
+      {/* False Handle */}
+      <div className="absolute right-[-6px] bottom-[30px] flex items-center flex-row-reverse">
+        <Handle
+          id="false"
+          type="source"
+          position={Position.Right}
+          className="w-3 h-3 bg-red-500 bottom-[25px]"
+        />
+        <span className="text-[10px] text-red-600 font-bold mr-2 mb-[12px]">False</span>
+      </div>
+
+      <ErrorHandle />
+    </Card>
+  );
+}
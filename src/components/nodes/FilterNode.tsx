@@ -6,11 +6,14 @@ import { Button } from "../ui/button";
 import { Card } from "../ui/card";
 import { Badge } from "../ui/badge";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "../ui/select";
+import ErrorHandle from "./ErrorHandle";
 
 export interface FilterNodeData {
     property: string;
-    condition: "equals" | "notEquals" | "contains" | "regex" | "extension" | "exists" | "notExists";
+    condition: "equals" | "notEquals" | "contains" | "regex" | "extension" | "exists" | "notExists" | "expression";
     value: string;
+    /** Used when `condition` is "expression"; sees each item's fields by name */
+    expression?: string;
     onSave: (id: string, data: FilterNodeData) => void;
 }
 
@@ -20,6 +23,7 @@ export default function FilterNode({ data, id }: { data: FilterNodeData; id: str
         property: data.property || "",
         condition: data.condition || "equals",
         value: data.value || "",
+        expression: data.expression || "",
         onSave: data.onSave
     });
 
@@ -47,7 +51,7 @@ export default function FilterNode({ data, id }: { data: FilterNodeData; id: str
                         <Input
                             value={editData.property}
                             onChange={(e) => setEditData({ ...editData, property: e.target.value })}
-                            placeholder="e.g. url or name (empty for root)"
+                            placeholder="e.g. url, or $.meta.name (empty for root)"
                             className="h-8 text-xs font-mono"
                         />
                     </div>
@@ -66,11 +70,24 @@ export default function FilterNode({ data, id }: { data: FilterNodeData; id: str
                                 <SelectItem value="extension">Extension (comma separated)</SelectItem>
                                 <SelectItem value="exists">Exists (not null)</SelectItem>
                                 <SelectItem value="notExists">Not Exists (null/missing)</SelectItem>
+                                <SelectItem value="expression">Expression</SelectItem>
                             </SelectContent>
                         </Select>
                     </div>
 
-                    {(editData.condition !== "exists" && editData.condition !== "notExists") && (
+                    {editData.condition === "expression" && (
+                        <div className="grid gap-1">
+                            <label className="text-[10px] text-muted-foreground uppercase">Expression</label>
+                            <Input
+                                value={editData.expression}
+                                onChange={(e) => setEditData({ ...editData, expression: e.target.value })}
+                                placeholder="e.g. age >= 18 && item.name.startsWith('A')"
+                                className="h-8 text-xs font-mono"
+                            />
+                        </div>
+                    )}
+
+                    {(editData.condition !== "exists" && editData.condition !== "notExists" && editData.condition !== "expression") && (
                         <div className="grid gap-1">
                             <label className="text-[10px] text-muted-foreground uppercase">Value</label>
                             <Input
@@ -85,6 +102,7 @@ export default function FilterNode({ data, id }: { data: FilterNodeData; id: str
 
                 <Handle type="target" position={Position.Top} className="w-2 h-2" />
                 <Handle type="source" position={Position.Bottom} className="w-2 h-2" />
+                <ErrorHandle />
             </Card>
         );
     }
@@ -109,11 +127,12 @@ export default function FilterNode({ data, id }: { data: FilterNodeData; id: str
                     </div>
                     <div className="flex justify-between pt-1">
                         <span className="text-muted-foreground italic">{editData.condition}:</span>
-                        <span className="font-mono truncate max-w-[100px] text-red-300">{editData.value}</span>
+                        <span className="font-mono truncate max-w-[100px] text-red-300">{editData.condition === "expression" ? editData.expression : editData.value}</span>
                     </div>
                 </div>
             </div>
             <Handle type="source" position={Position.Bottom} className="w-2 h-2" />
+            <ErrorHandle />
         </Card>
     );
 }
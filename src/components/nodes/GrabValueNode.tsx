@@ -1,39 +1,44 @@
 import { Handle, Position, useEdges, useNodes } from "reactflow";
 import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
 import { Card, CardContent, CardHeader, CardTitle } from "../ui/card";
 import { Input } from "../ui/input";
 import { Label } from "../ui/label";
 export interface GrabValueNodeData {
   key: string;
   id: string;
+  path?: string;
 }
 
-export default function GrabValueNode() {
-  const [path] = useState('');
-  const [selectedValue, setSelectedValue] = useState(null);
+export default function GrabValueNode({ id, data }: { id: string, data: GrabValueNodeData }) {
+  const [path, setPath] = useState(data?.path || '');
+  const [selectedValue, setSelectedValue] = useState<any>(null);
   const [error, setError] = useState('');
   const edges = useEdges();
   const nodes = useNodes();
 
-  const data = nodes.find((node) => node.id === edges.find((edge) => edge.target === 'value')?.source);
-  const parentNodeData = data?.data;
-  //find connected node
-  // const parentNodeData = nodes.find((node) => node.id === edges.find((edge) => edge.target === 'value')?.source)?.data;
-  useEffect(() => {
-    console.log('parentNodeData', edges);
-  }, [data]);
+  // The connected node's last output, or its settings before it has run
+  const parent = nodes.find((node) => node.id === edges.find((edge) => edge.target === id)?.source);
+  const parentNodeData = parent?.data?.executionResult?.output ?? parent?.data;
+
+  const onPathChange = (val: string) => {
+    setPath(val);
+    if (data) data.path = val;
+  };
 
+  // Same JSONPath evaluator the capture and arrayMap nodes use
   useEffect(() => {
-    if (!data) return;
+    if (!parentNodeData) return;
 
-    try {
-      const value = eval(`parentNodeData.${path}`);
-      setSelectedValue(value);
-      setError('');
-    } catch (e) {
-      setSelectedValue(null);
-      // setError(e.message as string);
-    }
+    invoke("query_json", { value: parentNodeData, path })
+      .then((value: any) => {
+        setSelectedValue(value);
+        setError('');
+      })
+      .catch((e) => {
+        setSelectedValue(null);
+        setError(String(e));
+      });
   }, [path, parentNodeData]);
 
 
@@ -55,10 +60,12 @@ export default function GrabValueNode() {
       <CardContent>
         <div className="space-y-4">
           <div className="text-inherit">
-            <Label htmlFor="path">Path (dot notation)</Label>
+            <Label htmlFor="path">Path (JSONPath)</Label>
             <Input
               id="path"
-              placeholder="e.g. data.users[0].name"
+              value={path}
+              onChange={(e) => onPathChange(e.target.value)}
+              placeholder="e.g. $.data.users[*].name"
               className="mt-1"
             />
           </div>
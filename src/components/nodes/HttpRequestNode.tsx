@@ -17,6 +17,7 @@ import {
   SelectTrigger,
   SelectValue,
 } from "../ui/select";
+import ErrorHandle from "./ErrorHandle";
 
 export interface HttpRequestNodeData {
   method: "GET" | "POST" | "PATCH" | "DELETE" | "PUT";
@@ -255,6 +256,7 @@ export default function HttpRequestNode({
 
         <Handle type="target" position={Position.Top} className="opacity-0" />
         <Handle type="source" position={Position.Bottom} className="opacity-0" />
+        <ErrorHandle />
       </Card>
     );
   }
@@ -262,6 +264,8 @@ export default function HttpRequestNode({
   return (
     <Card className={`min-w-[300px] bg-white shadow-sm dark:bg-gray-900 dark:text-gray-300 ${statusColor}`}>
       <Handle type="target" position={Position.Top} className="w-3 h-3 bg-gray-400" />
+      <Handle type="target" position={Position.Left} id="body" className="w-3 h-3 bg-blue-400" style={{ top: "35%" }} title="body" />
+      <Handle type="target" position={Position.Left} id="headers" className="w-3 h-3 bg-purple-400" style={{ top: "65%" }} title="headers" />
       <div className="p-3 space-y-3">
         <div className="flex items-center justify-between">
           <div className="flex items-center gap-2">
@@ -296,6 +300,7 @@ export default function HttpRequestNode({
       </div>
       <Handle type="source" position={Position.Bottom} id="success" className="w-3 h-3 bg-green-500" style={{ left: "30%" }} />
       <Handle type="source" position={Position.Bottom} id="failure" className="w-3 h-3 bg-red-500" style={{ left: "70%" }} />
+      <ErrorHandle />
     </Card>
   );
 }
@@ -5,6 +5,7 @@ import { Label } from "../ui/label";
 import { Input } from "../ui/input";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "../ui/select";
 import { InputNodeData } from "../../types";
+import ErrorHandle from "./ErrorHandle";
 
 export default memo(({ data, id }: { data: InputNodeData; id: string }) => {
     const [key, setKey] = useState(data.key || "");
@@ -59,6 +60,7 @@ export default memo(({ data, id }: { data: InputNodeData; id: string }) => {
                 </div>
             </CardContent>
             <Handle type="source" position={Position.Right} className="w-3 h-3 bg-blue-500" />
+            <ErrorHandle />
         </Card>
     );
 });
@@ -2,6 +2,7 @@ import { memo } from "react";
 import { Handle, Position } from "reactflow";
 import { Card, CardContent, CardHeader, CardTitle } from "../ui/card";
 import { RefreshCw } from "lucide-react";
+import ErrorHandle from "./ErrorHandle";
 
 export default memo(({ id: _id, data: _data }: { id: string, data: any }) => {
     return (
@@ -42,6 +43,7 @@ export default memo(({ id: _id, data: _data }: { id: string, data: any }) => {
                     <span className="text-[10px] text-green-600 font-bold mr-2 mb-[2px]">Done</span>
                 </div>
             </CardContent>
+            <ErrorHandle />
         </Card>
     );
 });
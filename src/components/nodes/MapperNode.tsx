@@ -4,6 +4,7 @@ import { Label } from "../ui/label";
 import { Textarea } from "../ui/textarea";
 import { Input } from "../ui/input";
 import { useState } from "react";
+import ErrorHandle from "./ErrorHandle";
 
 export default function MapperNode({ data }: { data: any }) {
     const [mappingStr, setMappingStr] = useState(JSON.stringify(data.mapping || {}, null, 2));
@@ -52,6 +53,7 @@ export default function MapperNode({ data }: { data: any }) {
                 </div>
             </CardContent>
             <Handle type="source" position={Position.Right} />
+            <ErrorHandle />
         </Card>
     );
 }
@@ -1,6 +1,7 @@
 import { memo } from "react";
 import { Handle, Position } from "reactflow";
 import { Card, CardContent } from "../ui/card";
+import ErrorHandle from "./ErrorHandle";
 
 export default memo(({ data }: { data: { label?: string } }) => {
     return (
@@ -10,6 +11,7 @@ export default memo(({ data }: { data: { label?: string } }) => {
                     {data.label || "Output"} 🏁
                 </CardContent>
                 <Handle type="target" position={Position.Left} className="w-3 h-3 bg-green-500" />
+                <ErrorHandle />
             </Card>
         </div>
     );
@@ -5,6 +5,7 @@ import { Input } from "../ui/input";
 import { Button } from "../ui/button";
 import { Card } from "../ui/card";
 import { Badge } from "../ui/badge";
+import ErrorHandle from "./ErrorHandle";
 
 export interface ScraperRule {
     selector: string;
@@ -111,6 +112,7 @@ export default function ScraperNode({ data, id }: { data: ScraperNodeData; id: s
 
                 <Handle type="target" position={Position.Top} className="w-2 h-2" />
                 <Handle type="source" position={Position.Bottom} className="w-2 h-2" />
+                <ErrorHandle />
             </Card>
         );
     }
@@ -149,6 +151,7 @@ export default function ScraperNode({ data, id }: { data: ScraperNodeData; id: s
                 </div>
             </div>
             <Handle type="source" position={Position.Bottom} className="w-2 h-2" />
+            <ErrorHandle />
         </Card>
     );
 }
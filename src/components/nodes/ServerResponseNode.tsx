@@ -5,6 +5,7 @@ import { Input } from "../ui/input";
 import { Textarea } from "../ui/textarea";
 import { Server } from "lucide-react";
 import { useState } from "react";
+import ErrorHandle from "./ErrorHandle";
 
 export default function ServerResponseNode({ data, id }: NodeProps) {
     const [status, setStatus] = useState(data.status || 200);
@@ -56,6 +57,7 @@ export default function ServerResponseNode({ data, id }: NodeProps) {
                 position={Position.Left}
                 className="w-3 h-3 bg-emerald-500"
             />
+            <ErrorHandle />
         </Card>
     );
 }
@@ -5,6 +5,7 @@ import { Input } from "../ui/input";
 import { Select, SelectContent, SelectItem, SelectTrigger, SelectValue } from "../ui/select";
 import { Server } from "lucide-react";
 import { useState } from "react";
+import ErrorHandle from "./ErrorHandle";
 
 const SUPPORTED_METHODS = ["GET", "POST", "PUT", "DELETE", "PATCH"];
 
@@ -75,6 +76,7 @@ export default function ServerTriggerNode({ data, id }: NodeProps) {
                 position={Position.Right}
                 className="w-3 h-3 bg-emerald-500"
             />
+            <ErrorHandle />
         </Card>
     );
 }
@@ -2,6 +2,7 @@ import { memo } from "react";
 import { Handle, Position } from "reactflow";
 import { Card, CardContent } from "../ui/card";
 import { Play } from "lucide-react";
+import ErrorHandle from "./ErrorHandle";
 
 export default memo(() => {
     return (
@@ -11,6 +12,7 @@ export default memo(() => {
                     <Play className="fill-current w-4 h-4" /> Start
                 </CardContent>
                 <Handle type="source" position={Position.Right} className="w-4 h-4 bg-white border-2 border-blue-600" />
+                <ErrorHandle />
             </Card>
         </div>
     );
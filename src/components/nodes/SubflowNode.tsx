@@ -0,0 +1,105 @@
+import { Handle, Position } from "reactflow";
+import { Card, CardHeader, CardContent } from "../ui/card";
+import { Label } from "../ui/label";
+import { Textarea } from "../ui/textarea";
+import { Input } from "../ui/input";
+import { useState } from "react";
+import ErrorHandle from "./ErrorHandle";
+
+export default function SubflowNode({ data }: { data: any }) {
+    const [path, setPath] = useState(data.path || "");
+    const [inputsStr, setInputsStr] = useState(JSON.stringify(data.inputs || {}, null, 2));
+    const [outputsStr, setOutputsStr] = useState(data.outputs ? JSON.stringify(data.outputs, null, 2) : "");
+    const [results, setResults] = useState((data.results || []).join(", "));
+
+    const save = () => {
+        if (data.onSave) data.onSave(data.id, data);
+    };
+
+    const onPathChange = (val: string) => {
+        setPath(val);
+        data.path = val;
+        save();
+    };
+
+    const onInputsChange = (val: string) => {
+        setInputsStr(val);
+        try {
+            data.inputs = JSON.parse(val);
+            save();
+        } catch (e) {
+            // Invalid JSON, don't save yet
+        }
+    };
+
+    // Leaving outputs empty exposes every variable of the subflow
+    const onOutputsChange = (val: string) => {
+        setOutputsStr(val);
+        if (!val.trim()) {
+            delete data.outputs;
+            save();
+            return;
+        }
+        try {
+            data.outputs = JSON.parse(val);
+            save();
+        } catch (e) {
+            // Invalid JSON, don't save yet
+        }
+    };
+
+    const onResultsChange = (val: string) => {
+        setResults(val);
+        data.results = val.split(",").map((id) => id.trim()).filter(Boolean);
+        save();
+    };
+
+    return (
+        <Card className="min-w-[250px] shadow-lg border-indigo-200 dark:border-indigo-900">
+            <Handle type="target" position={Position.Left} />
+            <CardHeader className="text-xs font-bold uppercase tracking-wider text-indigo-600 dark:text-indigo-400">
+                Subflow
+            </CardHeader>
+            <CardContent className="space-y-3">
+                <div className="space-y-1">
+                    <Label className="text-[10px]">Flow File</Label>
+                    <Input
+                        value={path}
+                        onChange={(e) => onPathChange(e.target.value)}
+                        className="text-[10px] h-7 font-mono"
+                        placeholder="flows/login.json"
+                    />
+                </div>
+                <div className="space-y-1">
+                    <Label className="text-[10px]">Inputs (subflow variable → value)</Label>
+                    <Textarea
+                        value={inputsStr}
+                        onChange={(e) => onInputsChange(e.target.value)}
+                        className="font-mono text-[10px] h-20 bg-slate-50 dark:bg-slate-900"
+                        placeholder='{ "user": "{{username}}" }'
+                    />
+                </div>
+                <div className="space-y-1">
+                    <Label className="text-[10px]">Outputs (name → subflow variable)</Label>
+                    <Textarea
+                        value={outputsStr}
+                        onChange={(e) => onOutputsChange(e.target.value)}
+                        className="font-mono text-[10px] h-20 bg-slate-50 dark:bg-slate-900"
+                        placeholder='All variables when empty, e.g. { "token": "authToken" }'
+                    />
+                </div>
+                <div className="space-y-1">
+                    <Label className="text-[10px]">Node Results (ids)</Label>
+                    <Input
+                        value={results}
+                        onChange={(e) => onResultsChange(e.target.value)}
+                        className="text-[10px] h-7 font-mono"
+                        placeholder="login-request, parse-token"
+                    />
+                </div>
+            </CardContent>
+            <Handle type="source" position={Position.Right} />
+            <ErrorHandle />
+        </Card>
+    );
+}
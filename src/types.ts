@@ -20,8 +20,9 @@ export interface ValueSelector {
 }
 export interface Conditional {
   input?: any
-  condition?: "equal" | "notEqual" | "greaterThan" | "lessThan" | "contains"
+  condition?: "equal" | "notEqual" | "greaterThan" | "lessThan" | "contains" | "expression"
   targetValue?: string
+  expression?: string
 }
 
 export interface InputNodeData {
@@ -76,8 +77,9 @@ export interface ScraperNodeData {
 
 export interface FilterNodeData {
   property: string
-  condition: "equals" | "notEquals" | "contains" | "regex" | "extension"
+  condition: "equals" | "notEquals" | "contains" | "regex" | "extension" | "exists" | "notExists" | "expression"
   value: string
+  expression?: string
   onSave?: (id: string, newData: FilterNodeData) => void
 }
 
@@ -91,8 +93,9 @@ export interface ArrayMapNodeData {
 }
 
 export interface AssertNodeData {
-  condition: "equals" | "notEquals" | "contains" | "notContains" | "greaterThan" | "lessThan" | "regex"
+  condition: "equals" | "notEquals" | "contains" | "notContains" | "greaterThan" | "lessThan" | "regex" | "expression"
   value: string
+  expression?: string
   message?: string
   onSave?: (id: string, newData: AssertNodeData) => void
 }
@@ -118,6 +121,23 @@ export interface ServerResponseNodeData {
   onSave?: (id: string, newData: ServerResponseNodeData) => void
 }
 
+export interface SubflowNodeData {
+  path: string
+  /** Subflow variable name to value; strings may use {{variables}} */
+  inputs?: Record<string, any>
+  /** Output name to subflow variable; all variables when omitted */
+  outputs?: Record<string, string>
+  /** Ids of subflow nodes whose outputs are passed on */
+  results?: string[]
+  onSave?: (id: string, newData: SubflowNodeData) => void
+}
+
+export interface ExpressionNodeData {
+  /** e.g. `status == 200 && vars.retries < 3`; routes to the true or false handle */
+  expression: string
+  onSave?: (id: string, newData: ExpressionNodeData) => void
+}
+
 export type NodeData =
   | HttpRequestNodeData
   | ApiResponseNodeData
@@ -139,6 +159,8 @@ export type NodeData =
   | GroupNodeData
   | ServerTriggerNodeData
   | ServerResponseNodeData
+  | SubflowNodeData
+  | ExpressionNodeData
 
 export interface FlowNode extends Node {
   data: NodeData
@@ -150,17 +172,91 @@ export interface NodeType {
   data: NodeData
 }
 
+export type ExecutionStatus = "pending" | "running" | "success" | "error" | "skipped" | "cancelled"
+
+export interface Attempt {
+  attempt: number
+  status: ExecutionStatus
+  http_status?: number
+  error?: string
+  duration_ms: number
+  retry_in_ms?: number
+}
+
 export interface ExecutionResult {
   node_id: string
-  status: "success" | "error" | "skipped"
+  status: ExecutionStatus
   output: any
-  error?: string
+  error: string | null
+  active_handle: string | null
+  iteration: number
+  attempt_count: number
+  /** Milliseconds since the Unix epoch */
+  started_at: number | null
+  finished_at: number | null
+  duration_ms: number | null
+  attempts: Attempt[]
+}
+
+export interface FlowRun {
+  /** Latest result of each node */
+  results: Record<string, ExecutionResult>
+  variables: Record<string, any>
+  /** Every node run in the order it finished, one entry per loop iteration */
+  trace: ExecutionResult[]
+  /** Final values of the flow's declared outputs */
+  outputs: Record<string, any>
+}
+
+/** Snapshot of a run stopped by the debugger in front of `node_id` */
+export interface PausedState {
+  node_id: string
+  variables: Record<string, any>
+  results: Record<string, ExecutionResult>
+}
+
+export type FlowEvent =
+  | { type: "node_started"; node_id: string; node_type: string }
+  | { type: "node_finished"; result: ExecutionResult }
+  | { type: "edge_fired"; edge_id: string; source: string; target: string }
+  | { type: "variable_changed"; name: string; value: any }
+  | { type: "paused"; node_id: string }
+  | { type: "flow_finished"; status: ExecutionStatus; node_count: number }
+
+export interface FlowSettings {
+  timeoutMs?: number
+  maxVisits?: number
+  strictTemplates?: boolean
+  seed?: number
+}
+
+export interface FlowParameter {
+  name: string
+  type?: "string" | "number" | "boolean" | "json"
+  default?: any
+  required?: boolean
+  /** Entered masked and never echoed back */
+  secret?: boolean
+  description?: string
+}
+
+export interface FlowOutput {
+  name: string
+  description?: string
 }
 
 export interface Flow {
+  /** File format version; older files are upgraded when loaded */
+  version?: number
   nodes: Node[]
   edges: Edge[]
+  settings?: FlowSettings
+  parameters?: FlowParameter[]
+  outputs?: FlowOutput[]
 }
 
+/** Everything a flow file holds besides its graph */
+export type FlowMeta = Omit<Flow, "nodes" | "edges">
+
 
 